@@ -0,0 +1,230 @@
+use crate::{
+    analog_in::{AnalogInput, AnalogInputError, ControlMode},
+    common::Channel,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+// Readings below this in 4~20mA mode mean the current loop is broken
+pub const OPEN_LOOP_THRESHOLD: f64 = 3800.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AlarmKind {
+    HighHigh,
+    High,
+    Low,
+    LowLow,
+    OpenLoop,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AlarmTransition {
+    Raised,
+    Cleared,
+    Acknowledged,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AlarmEvent {
+    pub channel: Channel,
+    pub kind: AlarmKind,
+    pub transition: AlarmTransition,
+    pub value: f64,
+    pub timestamp: Instant,
+}
+
+/// Limits are in the units reported for the channel's `ControlMode` (mV or uA).
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct AlarmConfig {
+    pub high_high: Option<f64>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    pub low_low: Option<f64>,
+    // Changes smaller than this are ignored
+    pub deadband: f64,
+    // A limit must be recovered by this much before the alarm clears
+    pub hysteresis: f64,
+    pub delay_on: Duration,
+    pub delay_off: Duration,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct AlarmState {
+    pub active: bool,
+    pub acknowledged: bool,
+    pending_since: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+struct ChannelAlarms {
+    config: AlarmConfig,
+    open_loop_detection: bool,
+    value: Option<f64>,
+    states: HashMap<AlarmKind, AlarmState>,
+}
+
+impl ChannelAlarms {
+    // Returns the (raise, clear) conditions for an alarm
+    fn conditions(&self, kind: AlarmKind, value: f64) -> Option<(bool, bool)> {
+        let hysteresis = self.config.hysteresis;
+        match kind {
+            AlarmKind::HighHigh => self
+                .config
+                .high_high
+                .map(|limit| (value > limit, value < limit - hysteresis)),
+            AlarmKind::High => self
+                .config
+                .high
+                .map(|limit| (value > limit, value < limit - hysteresis)),
+            AlarmKind::Low => self
+                .config
+                .low
+                .map(|limit| (value < limit, value > limit + hysteresis)),
+            AlarmKind::LowLow => self
+                .config
+                .low_low
+                .map(|limit| (value < limit, value > limit + hysteresis)),
+            AlarmKind::OpenLoop => self.open_loop_detection.then_some((
+                value < OPEN_LOOP_THRESHOLD,
+                value > OPEN_LOOP_THRESHOLD + hysteresis,
+            )),
+        }
+    }
+}
+
+/// Evaluates limit alarms for analog input channels.
+#[derive(Debug)]
+pub struct AlarmEngine {
+    channels: HashMap<Channel, ChannelAlarms>,
+    events: broadcast::Sender<AlarmEvent>,
+}
+
+impl Default for AlarmEngine {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(64);
+        AlarmEngine {
+            channels: HashMap::new(),
+            events,
+        }
+    }
+}
+
+impl AlarmEngine {
+    pub fn new() -> Self {
+        AlarmEngine::default()
+    }
+
+    pub fn configure(&mut self, channel: Channel, mode: ControlMode, config: AlarmConfig) {
+        self.channels.insert(
+            channel,
+            ChannelAlarms {
+                config,
+                open_loop_detection: mode == ControlMode::C4C20,
+                value: None,
+                states: HashMap::new(),
+            },
+        );
+    }
+
+    pub fn state(&self, channel: Channel, kind: AlarmKind) -> Option<AlarmState> {
+        self.channels
+            .get(&channel)
+            .and_then(|alarms| alarms.states.get(&kind))
+            .copied()
+    }
+
+    pub fn events(&self) -> impl Stream<Item = AlarmEvent> {
+        BroadcastStream::new(self.events.subscribe()).filter_map(|event| event.ok())
+    }
+
+    pub fn acknowledge(&mut self, channel: Channel, kind: AlarmKind) -> bool {
+        let Some(alarms) = self.channels.get_mut(&channel) else {
+            return false;
+        };
+        let Some(state) = alarms.states.get_mut(&kind) else {
+            return false;
+        };
+        if !state.active || state.acknowledged {
+            return false;
+        }
+        state.acknowledged = true;
+        let _ = self.events.send(AlarmEvent {
+            channel,
+            kind,
+            transition: AlarmTransition::Acknowledged,
+            value: alarms.value.unwrap_or_default(),
+            timestamp: Instant::now(),
+        });
+        true
+    }
+
+    pub async fn poll(
+        &mut self,
+        input: &mut AnalogInput,
+    ) -> Result<Vec<AlarmEvent>, AnalogInputError> {
+        let readings = input.read_input_channels().await?;
+        Ok(self.update(&readings, Instant::now()))
+    }
+
+    pub fn update(&mut self, readings: &[u16], now: Instant) -> Vec<AlarmEvent> {
+        let mut events = Vec::new();
+        for (channel, alarms) in self.channels.iter_mut() {
+            let Some(reading) = readings.get(*channel as usize) else {
+                continue;
+            };
+            let reading = *reading as f64;
+            let value = match alarms.value {
+                Some(last) if (reading - last).abs() < alarms.config.deadband => last,
+                _ => reading,
+            };
+            alarms.value = Some(value);
+
+            for kind in [
+                AlarmKind::HighHigh,
+                AlarmKind::High,
+                AlarmKind::Low,
+                AlarmKind::LowLow,
+                AlarmKind::OpenLoop,
+            ] {
+                let Some((raise, clear)) = alarms.conditions(kind, value) else {
+                    continue;
+                };
+                let state = alarms.states.entry(kind).or_default();
+                let (changing, delay) = if state.active {
+                    (clear, alarms.config.delay_off)
+                } else {
+                    (raise, alarms.config.delay_on)
+                };
+                if !changing {
+                    state.pending_since = None;
+                    continue;
+                }
+                let since = *state.pending_since.get_or_insert(now);
+                if now.saturating_duration_since(since) < delay {
+                    continue;
+                }
+                state.pending_since = None;
+                state.active = !state.active;
+                state.acknowledged = false;
+                events.push(AlarmEvent {
+                    channel: *channel,
+                    kind,
+                    transition: if state.active {
+                        AlarmTransition::Raised
+                    } else {
+                        AlarmTransition::Cleared
+                    },
+                    value,
+                    timestamp: now,
+                });
+            }
+        }
+        for event in &events {
+            let _ = self.events.send(*event);
+        }
+        events
+    }
+}
@@ -1,8 +1,14 @@
 use crate::{
-    common::{Baudrates, Channel, CommonHoldingRegisters, Parity, WaveshareModbus},
+    common::{
+        Baudrates, Channel, CommonHoldingRegisters, ControlModes, DeviceSettings, Parity,
+        SettingsError, WaveshareModbus, ANALOG_CHANNELS,
+    },
     ThreadSafeContext,
 };
+use std::fmt;
+use std::str::FromStr;
 use thiserror::Error;
+use tokio_modbus::Slave;
 
 #[derive(Error, Debug)]
 pub enum AnalogInputError {
@@ -12,6 +18,10 @@ pub enum AnalogInputError {
     ModbusError(tokio_modbus::Error),
     #[error("Invalid Control Mode")]
     InvalidControlMode,
+    #[error("Settings Error: `{0}`")]
+    Settings(#[from] SettingsError),
+    #[error("Invalid Channel: `{0:?}`")]
+    InvalidChannel(Channel),
 }
 
 #[derive(Debug)]
@@ -34,12 +44,18 @@ pub enum HoldingRegisterBases {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 pub enum ControlMode {
+    #[cfg_attr(feature = "serde", serde(rename = "0-10V"))]
     V0V10 = 0x0000, // 0~10V, output range: 0~5000mV;
+    #[cfg_attr(feature = "serde", serde(rename = "2-10V"))]
     V2V10 = 0x0001, // 2~10V, output range: 1000~5000mV;
+    #[cfg_attr(feature = "serde", serde(rename = "0-20mA"))]
     C0C20 = 0x0002, // 0~20mA, output range: 0~20000uA;
+    #[cfg_attr(feature = "serde", serde(rename = "4-20mA"))]
     C4C20 = 0x0003, // 4~20mA, output range: 4000~20000uA;
+    #[cfg_attr(feature = "serde", serde(rename = "raw"))]
     RAW = 0x0004, // directly output the value code, output range: 0~4096, the linear transformation is required to obtain the actual measured voltage and current.
 }
 
@@ -54,6 +70,39 @@ impl ControlMode {
             _ => Err(AnalogInputError::InvalidControlMode),
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlMode::V0V10 => "0-10V",
+            ControlMode::V2V10 => "2-10V",
+            ControlMode::C0C20 => "0-20mA",
+            ControlMode::C4C20 => "4-20mA",
+            ControlMode::RAW => "raw",
+        }
+    }
+}
+
+impl fmt::Display for ControlMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ControlMode {
+    type Err = AnalogInputError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        (0..=4)
+            .filter_map(|value| ControlMode::from_u16(value).ok())
+            .find(|mode| mode.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or(AnalogInputError::InvalidControlMode)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnalogInputSnapshot {
+    pub settings: DeviceSettings,
+    pub control_modes: Vec<ControlMode>,
 }
 
 impl AnalogInput {
@@ -61,20 +110,26 @@ impl AnalogInput {
         AnalogInput { unit_id, context }
     }
 
+    fn address(channel: Channel) -> Result<u16, AnalogInputError> {
+        channel
+            .analog_address()
+            .ok_or(AnalogInputError::InvalidChannel(channel))
+    }
+
     pub async fn set_slave_id(&mut self) {
-        self.context
-            .set_slave(tokio_modbus::Slave(self.unit_id))
-            .await;
+        self.context.set_slave(Slave(self.unit_id)).await;
     }
 
     pub async fn read_input_channel_status(
         &mut self,
         channel: Channel,
     ) -> Result<u16, AnalogInputError> {
-        self.set_slave_id().await;
+        let address = Self::address(channel)?;
         let result = self
             .context
-            .read_input_registers(channel as u16 + InputRegisterBases::InputChannels as u16, 1)
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_input_registers(address + InputRegisterBases::InputChannels as u16, 1)
             .await
             .map_err(|err| AnalogInputError::ModbusError(err))?
             .map_err(|err| AnalogInputError::ModbusException(err))?;
@@ -82,10 +137,14 @@ impl AnalogInput {
     }
 
     pub async fn read_input_channels(&mut self) -> Result<Vec<u16>, AnalogInputError> {
-        self.set_slave_id().await;
         let result = self
             .context
-            .read_input_registers(InputRegisterBases::InputChannels as u16, 8)
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_input_registers(
+                InputRegisterBases::InputChannels as u16,
+                ANALOG_CHANNELS as u16,
+            )
             .await
             .map_err(|err| AnalogInputError::ModbusError(err))?
             .map_err(|err| AnalogInputError::ModbusException(err))?;
@@ -97,10 +156,12 @@ impl AnalogInput {
         control_mode: ControlMode,
         channel: Channel,
     ) -> Result<(), AnalogInputError> {
-        self.set_slave_id().await;
+        let address = Self::address(channel)?;
         self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_register(
-                HoldingRegisterBases::AnalogMode as u16 + channel as u16,
+                HoldingRegisterBases::AnalogMode as u16 + address,
                 control_mode as u16,
             )
             .await
@@ -113,15 +174,61 @@ impl AnalogInput {
         &mut self,
         channel: Channel,
     ) -> Result<ControlMode, AnalogInputError> {
-        self.set_slave_id().await;
+        let address = Self::address(channel)?;
         let result = self
             .context
-            .read_holding_registers(HoldingRegisterBases::AnalogMode as u16 + channel as u16, 1)
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_holding_registers(HoldingRegisterBases::AnalogMode as u16 + address, 1)
             .await
             .map_err(|err| AnalogInputError::ModbusError(err))?
             .map_err(|err| AnalogInputError::ModbusException(err))?;
         ControlMode::from_u16(result[0])
     }
+
+    pub async fn read_control_modes(&mut self) -> Result<Vec<ControlMode>, AnalogInputError> {
+        self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_holding_registers(
+                HoldingRegisterBases::AnalogMode as u16,
+                ANALOG_CHANNELS as u16,
+            )
+            .await
+            .map_err(AnalogInputError::ModbusError)?
+            .map_err(AnalogInputError::ModbusException)?
+            .into_iter()
+            .map(ControlMode::from_u16)
+            .collect()
+    }
+
+    pub async fn snapshot(&mut self) -> Result<AnalogInputSnapshot, AnalogInputError> {
+        Ok(AnalogInputSnapshot {
+            settings: DeviceSettings::read(self).await?,
+            control_modes: self.read_control_modes().await?,
+        })
+    }
+
+    /// Writes and verifies the control modes, then `restore_settings`.
+    pub async fn restore(
+        &mut self,
+        snapshot: &AnalogInputSnapshot,
+    ) -> Result<(), AnalogInputError> {
+        self.restore_control_modes(&snapshot.control_modes).await?;
+        self.restore_settings(&snapshot.settings).await
+    }
+}
+
+impl ControlModes for AnalogInput {
+    type Mode = ControlMode;
+
+    async fn write_mode(&mut self, channel: Channel, mode: ControlMode) -> Result<(), Self::Error> {
+        self.write_control_mode(mode, channel).await
+    }
+
+    async fn read_modes(&mut self) -> Result<Vec<ControlMode>, Self::Error> {
+        self.read_control_modes().await
+    }
 }
 
 impl WaveshareModbus for AnalogInput {
@@ -132,10 +239,11 @@ impl WaveshareModbus for AnalogInput {
         baud: Baudrates,
         parity: Parity,
     ) -> Result<(), Self::Error> {
-        self.set_slave_id().await;
         let value = ((parity as u16) << 8) | (baud as u16);
         let result = self
             .context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_register(CommonHoldingRegisters::UartParameters as u16, value)
             .await
             .map_err(|err| AnalogInputError::ModbusError(err))?
@@ -144,9 +252,10 @@ impl WaveshareModbus for AnalogInput {
     }
 
     async fn set_device_address(&mut self, address: u8) -> Result<(), Self::Error> {
-        self.set_slave_id().await;
         let result = self
             .context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_register(CommonHoldingRegisters::DeviceAddress as u16, address as u16)
             .await
             .map_err(|err| AnalogInputError::ModbusError(err))?
@@ -155,13 +264,33 @@ impl WaveshareModbus for AnalogInput {
     }
 
     async fn read_software_version(&mut self) -> Result<u16, Self::Error> {
-        self.set_slave_id().await;
         let result = self
             .context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .read_holding_registers(CommonHoldingRegisters::SoftwareVersion as u16, 1)
             .await
             .map_err(|err| AnalogInputError::ModbusError(err))?
             .map_err(|err| AnalogInputError::ModbusException(err))?;
         Ok(result[0])
     }
+
+    async fn read_common_register(
+        &mut self,
+        register: CommonHoldingRegisters,
+    ) -> Result<u16, Self::Error> {
+        let result = self
+            .context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_holding_registers(register as u16, 1)
+            .await
+            .map_err(AnalogInputError::ModbusError)?
+            .map_err(AnalogInputError::ModbusException)?;
+        Ok(result[0])
+    }
+
+    fn set_unit_id(&mut self, unit_id: u8) {
+        self.unit_id = unit_id;
+    }
 }
@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+/// Order in which queued requests get the bus, highest first.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Safety,
+    #[default]
+    Control,
+    Polling,
+    Diagnostics,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 4] = [
+        Priority::Safety,
+        Priority::Control,
+        Priority::Polling,
+        Priority::Diagnostics,
+    ];
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct LevelMetrics {
+    pub queued: usize,
+    pub peak_queued: usize,
+    pub granted: u64,
+    // Grants made ahead of a higher priority because the waiter hit the starvation limit
+    pub promoted: u64,
+    pub longest_wait: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BusMetrics {
+    levels: [LevelMetrics; 4],
+}
+
+impl BusMetrics {
+    pub fn level(&self, priority: Priority) -> &LevelMetrics {
+        &self.levels[priority as usize]
+    }
+
+    pub fn queued(&self) -> usize {
+        self.levels.iter().map(|level| level.queued).sum()
+    }
+}
+
+#[derive(Debug)]
+struct Waiter {
+    queued_at: Instant,
+    sender: oneshot::Sender<Permit>,
+}
+
+#[derive(Debug)]
+struct State {
+    busy: bool,
+    queues: [VecDeque<Waiter>; 4],
+    starvation_limit: Duration,
+    metrics: BusMetrics,
+}
+
+impl State {
+    // Highest priority first, unless a waiter has been queued past the
+    // starvation limit, then the longest waiting of those goes first. Nothing
+    // is ever promoted over a queued `Safety` request.
+    fn next(&mut self) -> Option<oneshot::Sender<Permit>> {
+        for queue in &mut self.queues {
+            queue.retain(|waiter| !waiter.sender.is_closed());
+        }
+        let now = Instant::now();
+        let highest = self.queues.iter().position(|queue| !queue.is_empty())?;
+        let starved = if highest == Priority::Safety as usize {
+            None
+        } else {
+            self.queues
+                .iter()
+                .enumerate()
+                .filter_map(|(index, queue)| Some((index, queue.front()?.queued_at)))
+                .filter(|(_, queued_at)| now - *queued_at >= self.starvation_limit)
+                .min_by_key(|(_, queued_at)| *queued_at)
+                .map(|(index, _)| index)
+        };
+        let index = starved.unwrap_or(highest);
+        let waiter = self.queues[index].pop_front()?;
+        let level = &mut self.metrics.levels[index];
+        level.granted += 1;
+        if index != highest {
+            level.promoted += 1;
+        }
+        level.longest_wait = level.longest_wait.max(now - waiter.queued_at);
+        Some(waiter.sender)
+    }
+}
+
+/// Hands the bus to one user at a time in priority order.
+#[derive(Debug)]
+pub(crate) struct Arbiter {
+    state: Mutex<State>,
+}
+
+impl Arbiter {
+    pub(crate) fn new() -> Self {
+        Arbiter {
+            state: Mutex::new(State {
+                busy: false,
+                queues: Default::default(),
+                starvation_limit: Duration::from_secs(1),
+                metrics: BusMetrics::default(),
+            }),
+        }
+    }
+
+    pub(crate) fn set_starvation_limit(&self, limit: Duration) {
+        self.state.lock().unwrap().starvation_limit = limit;
+    }
+
+    pub(crate) fn metrics(&self) -> BusMetrics {
+        let state = self.state.lock().unwrap();
+        let mut metrics = state.metrics.clone();
+        for (level, queue) in metrics.levels.iter_mut().zip(&state.queues) {
+            level.queued = queue
+                .iter()
+                .filter(|waiter| !waiter.sender.is_closed())
+                .count();
+        }
+        metrics
+    }
+
+    pub(crate) async fn acquire(self: &Arc<Self>, priority: Priority) -> Permit {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+            if !state.busy && state.queues.iter().all(VecDeque::is_empty) {
+                state.busy = true;
+                state.metrics.levels[priority as usize].granted += 1;
+                return Permit {
+                    arbiter: Some(Arc::clone(self)),
+                };
+            }
+            let (sender, receiver) = oneshot::channel();
+            let queue = &mut state.queues[priority as usize];
+            queue.push_back(Waiter {
+                queued_at: Instant::now(),
+                sender,
+            });
+            let queued = queue.len();
+            let level = &mut state.metrics.levels[priority as usize];
+            level.peak_queued = level.peak_queued.max(queued);
+            receiver
+        };
+        // Waiters are only dropped once their receiver is closed
+        receiver.await.expect("bus arbiter dropped a waiter")
+    }
+
+    fn release(self: &Arc<Self>) {
+        loop {
+            let sender = {
+                let mut state = self.state.lock().unwrap();
+                match state.next() {
+                    Some(sender) => sender,
+                    None => {
+                        state.busy = false;
+                        return;
+                    }
+                }
+            };
+            let permit = Permit {
+                arbiter: Some(Arc::clone(self)),
+            };
+            // The waiter gave up between being picked and being sent the permit
+            if let Err(mut permit) = sender.send(permit) {
+                permit.arbiter = None;
+                continue;
+            }
+            return;
+        }
+    }
+}
+
+/// Turn on the bus, passed to the next waiter when dropped.
+#[derive(Debug)]
+pub(crate) struct Permit {
+    arbiter: Option<Arc<Arbiter>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(arbiter) = self.arbiter.take() {
+            arbiter.release();
+        }
+    }
+}
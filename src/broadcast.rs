@@ -0,0 +1,139 @@
+use crate::{
+    analog_out,
+    common::{Channel, ANALOG_CHANNELS},
+    digital::{Action, IoBank, OutputRegisterBases},
+    ThreadSafeContext,
+};
+use std::borrow::Cow;
+use std::time::Duration;
+use thiserror::Error;
+use tokio_modbus::Request;
+
+#[derive(Error, Debug)]
+pub enum BroadcastError {
+    #[error("Modbus Exception Error: `{0}`")]
+    ModbusException(tokio_modbus::ExceptionCode),
+    #[error("Modbus Error: `{0}`")]
+    ModbusError(tokio_modbus::Error),
+    #[error("Invalid Channel: `{0:?}`")]
+    InvalidChannel(Channel),
+}
+
+/// Writes to every module on the bus at once through unit 0.
+///
+/// Devices don't answer broadcasts, so there is nothing to read and no read
+/// methods. Each write holds the bus for the turnaround delay instead of
+/// waiting for a response, giving the modules time to act on it.
+#[derive(Debug)]
+pub struct Broadcast<const N: usize = 8> {
+    context: ThreadSafeContext,
+    turnaround: Duration,
+}
+
+impl Broadcast {
+    pub fn new(context: ThreadSafeContext) -> Self {
+        Broadcast::with_channels(context)
+    }
+}
+
+impl<const N: usize> Broadcast<N> {
+    pub fn with_channels(context: ThreadSafeContext) -> Self {
+        Broadcast {
+            context,
+            turnaround: Duration::from_millis(100),
+        }
+    }
+
+    /// How long the bus is kept idle after each write, 100ms by default.
+    pub fn with_turnaround(mut self, turnaround: Duration) -> Self {
+        self.turnaround = turnaround;
+        self
+    }
+
+    pub async fn write_output_channel(
+        &mut self,
+        channel: Channel,
+        action: Action,
+    ) -> Result<(), BroadcastError> {
+        if channel as usize >= N {
+            return Err(BroadcastError::InvalidChannel(channel));
+        }
+        self.send(Request::WriteSingleCoil(
+            channel as u16 + OutputRegisterBases::OutputChannel as u16,
+            action == Action::On,
+        ))
+        .await
+    }
+
+    pub async fn write_output_channels(
+        &mut self,
+        actions: [Action; N],
+    ) -> Result<(), BroadcastError> {
+        self.write_output_bank(IoBank::from(actions.map(|x| x == Action::On)))
+            .await
+    }
+
+    pub async fn write_output_bank(&mut self, bank: IoBank<N>) -> Result<(), BroadcastError> {
+        self.send(Request::WriteMultipleCoils(
+            OutputRegisterBases::OutputChannel as u16,
+            Cow::Owned(<[bool; N]>::from(bank).to_vec()),
+        ))
+        .await
+    }
+
+    pub async fn open_all_outputs(&mut self) -> Result<(), BroadcastError> {
+        self.send(Request::WriteSingleCoil(
+            OutputRegisterBases::ControlAllRegisters as u16,
+            true,
+        ))
+        .await
+    }
+
+    pub async fn close_all_outputs(&mut self) -> Result<(), BroadcastError> {
+        self.send(Request::WriteSingleCoil(
+            OutputRegisterBases::ControlAllRegisters as u16,
+            false,
+        ))
+        .await
+    }
+
+    pub async fn write_analog_output(
+        &mut self,
+        channel: Channel,
+        value: u16,
+    ) -> Result<(), BroadcastError> {
+        let address = channel
+            .analog_address()
+            .ok_or(BroadcastError::InvalidChannel(channel))?;
+        self.send(Request::WriteSingleRegister(
+            address + analog_out::HoldingRegisterBases::AnalogValue as u16,
+            value,
+        ))
+        .await
+    }
+
+    pub async fn write_analog_outputs(
+        &mut self,
+        start: Channel,
+        values: &[u16],
+    ) -> Result<(), BroadcastError> {
+        if start as usize + values.len() > ANALOG_CHANNELS {
+            return Err(BroadcastError::InvalidChannel(start));
+        }
+        self.send(Request::WriteMultipleRegisters(
+            start as u16 + analog_out::HoldingRegisterBases::AnalogValue as u16,
+            Cow::Borrowed(values),
+        ))
+        .await
+    }
+
+    async fn send(&self, request: Request<'_>) -> Result<(), BroadcastError> {
+        self.context
+            .lock()
+            .await
+            .broadcast(request, self.turnaround)
+            .await
+            .map_err(BroadcastError::ModbusError)?
+            .map_err(BroadcastError::ModbusException)
+    }
+}
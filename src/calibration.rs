@@ -0,0 +1,326 @@
+use crate::{
+    analog_in::{AnalogInput, AnalogInputError},
+    analog_out::{AnalogOutput, AnalogOutputError},
+    common::Channel,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CalibrationError {
+    #[error("Invalid Calibration Table: `{0}`")]
+    InvalidTable(String),
+    #[error("Calibration is not invertible")]
+    NotInvertible,
+    #[error("Value `{0}` is outside the output range")]
+    OutOfRange(f64),
+    #[error("Parse Error: `{0}`")]
+    Parse(String),
+    #[error("IO Error: `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("Analog Input Error: `{0}`")]
+    AnalogInput(#[from] AnalogInputError),
+    #[error("Analog Output Error: `{0}`")]
+    AnalogOutput(#[from] AnalogOutputError),
+}
+
+/// Maps raw device values onto engineering units.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Calibration {
+    // value = raw * gain + offset
+    Linear { offset: f64, gain: f64 },
+    Table(CalibrationTable),
+}
+
+/// (raw, value) pairs sorted by raw value, interpolated piecewise-linearly.
+///
+/// Built through `Calibration::table`, which checks there are at least two
+/// points and that they are monotonic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibrationTable {
+    points: Vec<(f64, f64)>,
+}
+
+impl CalibrationTable {
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+}
+
+impl Calibration {
+    pub fn linear(offset: f64, gain: f64) -> Self {
+        Calibration::Linear { offset, gain }
+    }
+
+    pub fn table(mut points: Vec<(f64, f64)>) -> Result<Self, CalibrationError> {
+        if points.len() < 2 {
+            return Err(CalibrationError::InvalidTable(
+                "at least two points are required".to_string(),
+            ));
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let increasing = points
+            .windows(2)
+            .all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1);
+        let decreasing = points
+            .windows(2)
+            .all(|w| w[0].0 < w[1].0 && w[0].1 > w[1].1);
+        if !increasing && !decreasing {
+            return Err(CalibrationError::InvalidTable(
+                "points must be strictly monotonic".to_string(),
+            ));
+        }
+        Ok(Calibration::Table(CalibrationTable { points }))
+    }
+
+    pub fn apply(&self, raw: f64) -> f64 {
+        match self {
+            Calibration::Linear { offset, gain } => raw * gain + offset,
+            Calibration::Table(table) => interpolate(&table.points, raw),
+        }
+    }
+
+    pub fn invert(&self, value: f64) -> Result<f64, CalibrationError> {
+        match self {
+            Calibration::Linear { offset, gain } => {
+                if *gain == 0.0 {
+                    return Err(CalibrationError::NotInvertible);
+                }
+                Ok((value - offset) / gain)
+            }
+            Calibration::Table(table) => {
+                let mut swapped: Vec<(f64, f64)> =
+                    table.points.iter().map(|(r, v)| (*v, *r)).collect();
+                swapped.sort_by(|a, b| a.0.total_cmp(&b.0));
+                Ok(interpolate(&swapped, value))
+            }
+        }
+    }
+}
+
+// Extrapolates from the first or last segment outside the table
+fn interpolate(points: &[(f64, f64)], x: f64) -> f64 {
+    let index = points
+        .windows(2)
+        .position(|w| x <= w[1].0)
+        .unwrap_or(points.len().saturating_sub(2));
+    let (x0, y0) = points[index];
+    let (x1, y1) = points[index + 1];
+    y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+}
+
+impl fmt::Display for Calibration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Calibration::Linear { offset, gain } => write!(f, "linear {} {}", offset, gain),
+            Calibration::Table(table) => {
+                write!(f, "table")?;
+                for (raw, value) in &table.points {
+                    write!(f, " {}:{}", raw, value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for Calibration {
+    type Err = CalibrationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CalibrationError::Parse(s.to_string());
+        let number = |field: &str| field.parse::<f64>().map_err(|_| invalid());
+        let mut fields = s.split_whitespace();
+        match fields.next() {
+            Some("linear") => {
+                let offset = number(fields.next().ok_or_else(invalid)?)?;
+                let gain = number(fields.next().ok_or_else(invalid)?)?;
+                Ok(Calibration::linear(offset, gain))
+            }
+            Some("table") => {
+                let points = fields
+                    .map(|point| {
+                        let (raw, value) = point.split_once(':').ok_or_else(invalid)?;
+                        Ok((number(raw)?, number(value)?))
+                    })
+                    .collect::<Result<Vec<_>, CalibrationError>>()?;
+                Calibration::table(points)
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Per-channel calibrations for one analog module.
+///
+/// Persisted as one `<channel> <calibration>` line per channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CalibrationSet {
+    channels: BTreeMap<u16, Calibration>,
+}
+
+impl CalibrationSet {
+    pub fn new() -> Self {
+        CalibrationSet::default()
+    }
+
+    pub fn set(&mut self, channel: Channel, calibration: Calibration) {
+        self.channels.insert(channel as u16, calibration);
+    }
+
+    pub fn get(&self, channel: Channel) -> Option<&Calibration> {
+        self.channels.get(&(channel as u16))
+    }
+
+    pub fn remove(&mut self, channel: Channel) -> Option<Calibration> {
+        self.channels.remove(&(channel as u16))
+    }
+
+    pub fn apply(&self, channel: Channel, raw: u16) -> f64 {
+        match self.get(channel) {
+            Some(calibration) => calibration.apply(raw as f64),
+            None => raw as f64,
+        }
+    }
+
+    pub fn invert(&self, channel: Channel, value: f64) -> Result<u16, CalibrationError> {
+        let raw = match self.get(channel) {
+            Some(calibration) => calibration.invert(value)?,
+            None => value,
+        };
+        if !(0.0..=u16::MAX as f64).contains(&raw.round()) {
+            return Err(CalibrationError::OutOfRange(value));
+        }
+        Ok(raw.round() as u16)
+    }
+
+    pub async fn read_input_channels(
+        &self,
+        input: &mut AnalogInput,
+    ) -> Result<Vec<f64>, CalibrationError> {
+        let readings = input.read_input_channels().await?;
+        Ok(readings
+            .iter()
+            .enumerate()
+            .map(|(index, raw)| match Channel::try_from(index as u8) {
+                Ok(channel) => self.apply(channel, *raw),
+                Err(_) => *raw as f64,
+            })
+            .collect())
+    }
+
+    pub async fn write_output_channel_value(
+        &self,
+        output: &mut AnalogOutput,
+        channel: Channel,
+        value: f64,
+    ) -> Result<(), CalibrationError> {
+        let raw = self.invert(channel, value)?;
+        output.write_output_channel_value(channel, raw).await?;
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CalibrationError> {
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CalibrationError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+}
+
+impl fmt::Display for CalibrationSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (channel, calibration) in &self.channels {
+            writeln!(f, "{} {}", channel, calibration)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CalibrationSet {
+    type Err = CalibrationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = CalibrationSet::new();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || CalibrationError::Parse(line.to_string());
+            let (channel, calibration) = line.split_once(' ').ok_or_else(invalid)?;
+            let channel = channel
+                .parse::<u8>()
+                .ok()
+                .and_then(|channel| Channel::try_from(channel).ok())
+                .ok_or_else(invalid)?;
+            set.set(channel, calibration.parse()?);
+        }
+        Ok(set)
+    }
+}
+
+/// Captures reference points for one input channel.
+///
+/// Apply a known reference signal, call `capture` with its value, repeat for
+/// each point and then `finish` to build the calibration.
+#[derive(Debug, Clone)]
+pub struct CalibrationRoutine {
+    pub channel: Channel,
+    pub samples_per_point: usize,
+    pub sample_interval: Duration,
+    points: Vec<(f64, f64)>,
+}
+
+impl CalibrationRoutine {
+    pub fn new(channel: Channel) -> Self {
+        CalibrationRoutine {
+            channel,
+            samples_per_point: 10,
+            sample_interval: Duration::from_millis(100),
+            points: Vec::new(),
+        }
+    }
+
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    // Returns the averaged raw reading recorded for the reference
+    pub async fn capture(
+        &mut self,
+        input: &mut AnalogInput,
+        reference: f64,
+    ) -> Result<f64, CalibrationError> {
+        let samples = self.samples_per_point.max(1);
+        let mut total = 0.0;
+        for sample in 0..samples {
+            if sample > 0 {
+                tokio::time::sleep(self.sample_interval).await;
+            }
+            total += input.read_input_channel_status(self.channel).await? as f64;
+        }
+        let raw = total / samples as f64;
+        self.points.push((raw, reference));
+        Ok(raw)
+    }
+
+    // Two points give an offset/gain calibration, more give a table
+    pub fn finish(self) -> Result<Calibration, CalibrationError> {
+        if let [(raw0, value0), (raw1, value1)] = self.points[..] {
+            if raw0 == raw1 {
+                return Err(CalibrationError::InvalidTable(
+                    "reference points have identical readings".to_string(),
+                ));
+            }
+            let gain = (value1 - value0) / (raw1 - raw0);
+            return Ok(Calibration::linear(value0 - raw0 * gain, gain));
+        }
+        Calibration::table(self.points)
+    }
+}
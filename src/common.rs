@@ -1,3 +1,8 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
 #[derive(Debug, Copy, Clone)]
 #[repr(u16)]
 pub enum CommonHoldingRegisters {
@@ -6,7 +11,13 @@ pub enum CommonHoldingRegisters {
     SoftwareVersion = 0x8000,
 }
 
-#[derive(Debug, Copy, Clone)]
+// Serialised as bits per second, e.g. `115200`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "u32", try_from = "u32")
+)]
 #[repr(u16)]
 pub enum Baudrates {
     B4800 = 0x00,
@@ -19,7 +30,12 @@ pub enum Baudrates {
     B256000 = 0x07,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 #[repr(u16)]
 pub enum Parity {
     None = 0x00,
@@ -27,7 +43,195 @@ pub enum Parity {
     Odd = 0x02,
 }
 
-#[derive(Debug, Copy, Clone)]
+impl TryFrom<u16> for Baudrates {
+    type Error = &'static str;
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(Baudrates::B4800),
+            0x01 => Ok(Baudrates::B9600),
+            0x02 => Ok(Baudrates::B19200),
+            0x03 => Ok(Baudrates::B38400),
+            0x04 => Ok(Baudrates::B57600),
+            0x05 => Ok(Baudrates::B115200),
+            0x06 => Ok(Baudrates::B128000),
+            0x07 => Ok(Baudrates::B256000),
+            _ => Err("Invalid Baudrate"),
+        }
+    }
+}
+
+impl Baudrates {
+    pub const ALL: [Baudrates; 8] = [
+        Baudrates::B4800,
+        Baudrates::B9600,
+        Baudrates::B19200,
+        Baudrates::B38400,
+        Baudrates::B57600,
+        Baudrates::B115200,
+        Baudrates::B128000,
+        Baudrates::B256000,
+    ];
+
+    pub fn bps(&self) -> u32 {
+        u32::from(*self)
+    }
+
+    /// The 3.5 character silence that delimits RTU frames, 11 bits per character.
+    ///
+    /// The spec fixes it at 1.75ms above 19200 baud.
+    pub fn silent_interval(&self) -> Duration {
+        if self.bps() > 19200 {
+            Duration::from_micros(1750)
+        } else {
+            Duration::from_secs_f64(3.5 * 11.0 / f64::from(self.bps()))
+        }
+    }
+}
+
+impl fmt::Display for Baudrates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.bps())
+    }
+}
+
+impl FromStr for Baudrates {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bps: u32 = s.trim().parse().map_err(|_| "Invalid Baudrate")?;
+        Baudrates::try_from(bps)
+    }
+}
+
+impl From<Baudrates> for u32 {
+    fn from(baudrate: Baudrates) -> Self {
+        match baudrate {
+            Baudrates::B4800 => 4800,
+            Baudrates::B9600 => 9600,
+            Baudrates::B19200 => 19200,
+            Baudrates::B38400 => 38400,
+            Baudrates::B57600 => 57600,
+            Baudrates::B115200 => 115200,
+            Baudrates::B128000 => 128000,
+            Baudrates::B256000 => 256000,
+        }
+    }
+}
+
+impl TryFrom<u32> for Baudrates {
+    type Error = &'static str;
+    fn try_from(bps: u32) -> Result<Self, Self::Error> {
+        match bps {
+            4800 => Ok(Baudrates::B4800),
+            9600 => Ok(Baudrates::B9600),
+            19200 => Ok(Baudrates::B19200),
+            38400 => Ok(Baudrates::B38400),
+            57600 => Ok(Baudrates::B57600),
+            115200 => Ok(Baudrates::B115200),
+            128000 => Ok(Baudrates::B128000),
+            256000 => Ok(Baudrates::B256000),
+            _ => Err("Unsupported Baudrate"),
+        }
+    }
+}
+
+impl TryFrom<u16> for Parity {
+    type Error = &'static str;
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(Parity::None),
+            0x01 => Ok(Parity::Even),
+            0x02 => Ok(Parity::Odd),
+            _ => Err("Invalid Parity"),
+        }
+    }
+}
+
+impl fmt::Display for Parity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Parity::None => write!(f, "none"),
+            Parity::Even => write!(f, "even"),
+            Parity::Odd => write!(f, "odd"),
+        }
+    }
+}
+
+impl FromStr for Parity {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" | "n" => Ok(Parity::None),
+            "even" | "e" => Ok(Parity::Even),
+            "odd" | "o" => Ok(Parity::Odd),
+            _ => Err("Invalid Parity"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UartParameters {
+    pub baudrate: Baudrates,
+    pub parity: Parity,
+}
+
+impl UartParameters {
+    // Parity in the high byte, baud rate in the low byte
+    pub fn from_register(value: u16) -> Option<Self> {
+        Some(UartParameters {
+            baudrate: Baudrates::try_from(value & 0xFF).ok()?,
+            parity: Parity::try_from(value >> 8).ok()?,
+        })
+    }
+
+    pub fn to_register(&self) -> u16 {
+        ((self.parity as u16) << 8) | (self.baudrate as u16)
+    }
+}
+
+/// The registers every Waveshare module shares.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceSettings {
+    pub uart: UartParameters,
+    pub address: u8,
+    // Recorded for reference, it can't be restored
+    pub software_version: u16,
+}
+
+/// Failures shared by every module when reading or restoring `DeviceSettings`.
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("Invalid UART Parameters: `{0:#06x}`")]
+    InvalidUartParameters(u16),
+    #[error("Restore Verification Failed: `{0}`")]
+    VerifyFailed(String),
+    #[error("Device can't read its common registers")]
+    Unsupported,
+}
+
+impl DeviceSettings {
+    pub async fn read<D>(device: &mut D) -> Result<Self, D::Error>
+    where
+        D: WaveshareModbus,
+        D::Error: From<SettingsError>,
+    {
+        Ok(DeviceSettings {
+            uart: device.read_uart_parameters().await?,
+            address: device.read_device_address().await?,
+            software_version: device.read_software_version().await?,
+        })
+    }
+}
+
+// Analog modules and the smaller IO boards only use the first 8 channels.
+// Serialised as the channel index.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "u8", try_from = "u8")
+)]
 #[repr(u16)]
 pub enum Channel {
     Channel0 = 0x0000,
@@ -38,22 +242,105 @@ pub enum Channel {
     Channel5 = 0x0005,
     Channel6 = 0x0006,
     Channel7 = 0x0007,
+    Channel8 = 0x0008,
+    Channel9 = 0x0009,
+    Channel10 = 0x000A,
+    Channel11 = 0x000B,
+    Channel12 = 0x000C,
+    Channel13 = 0x000D,
+    Channel14 = 0x000E,
+    Channel15 = 0x000F,
+    Channel16 = 0x0010,
+    Channel17 = 0x0011,
+    Channel18 = 0x0012,
+    Channel19 = 0x0013,
+    Channel20 = 0x0014,
+    Channel21 = 0x0015,
+    Channel22 = 0x0016,
+    Channel23 = 0x0017,
+    Channel24 = 0x0018,
+    Channel25 = 0x0019,
+    Channel26 = 0x001A,
+    Channel27 = 0x001B,
+    Channel28 = 0x001C,
+    Channel29 = 0x001D,
+    Channel30 = 0x001E,
+    Channel31 = 0x001F,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 32] = [
+        Channel::Channel0,
+        Channel::Channel1,
+        Channel::Channel2,
+        Channel::Channel3,
+        Channel::Channel4,
+        Channel::Channel5,
+        Channel::Channel6,
+        Channel::Channel7,
+        Channel::Channel8,
+        Channel::Channel9,
+        Channel::Channel10,
+        Channel::Channel11,
+        Channel::Channel12,
+        Channel::Channel13,
+        Channel::Channel14,
+        Channel::Channel15,
+        Channel::Channel16,
+        Channel::Channel17,
+        Channel::Channel18,
+        Channel::Channel19,
+        Channel::Channel20,
+        Channel::Channel21,
+        Channel::Channel22,
+        Channel::Channel23,
+        Channel::Channel24,
+        Channel::Channel25,
+        Channel::Channel26,
+        Channel::Channel27,
+        Channel::Channel28,
+        Channel::Channel29,
+        Channel::Channel30,
+        Channel::Channel31,
+    ];
+
+    /// The register offset of an analog channel, `None` past the end of the module
+    pub(crate) fn analog_address(self) -> Option<u16> {
+        (usize::from(self as u8) < ANALOG_CHANNELS).then_some(self as u16)
+    }
+}
+
+// Channels on the analog modules, `Channel` goes up to 31 for the wider digital modules
+pub const ANALOG_CHANNELS: usize = 8;
+
+impl From<Channel> for u8 {
+    fn from(channel: Channel) -> Self {
+        channel as u8
+    }
 }
 
 impl TryFrom<u8> for Channel {
     type Error = &'static str;
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value {
-            0 => Ok(Channel::Channel0),
-            1 => Ok(Channel::Channel1),
-            2 => Ok(Channel::Channel2),
-            3 => Ok(Channel::Channel3),
-            4 => Ok(Channel::Channel4),
-            5 => Ok(Channel::Channel5),
-            6 => Ok(Channel::Channel6),
-            7 => Ok(Channel::Channel7),
-            _ => Err("Invalid Channel"),
-        }
+        Channel::ALL
+            .get(value as usize)
+            .copied()
+            .ok_or("Invalid Channel")
+    }
+}
+
+// The channel index, e.g. `3`
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", *self as u8)
+    }
+}
+
+impl FromStr for Channel {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let index: u8 = s.trim().parse().map_err(|_| "Invalid Channel")?;
+        Channel::try_from(index)
     }
 }
 
@@ -69,6 +356,102 @@ pub trait WaveshareModbus {
     async fn set_device_address(&mut self, address: u8) -> Result<(), Self::Error>;
     #[allow(async_fn_in_trait)]
     async fn read_software_version(&mut self) -> Result<u16, Self::Error>;
+    // Drivers written before the settings reads keep compiling, they report
+    // `SettingsError::Unsupported` until they override this
+    #[allow(async_fn_in_trait)]
+    async fn read_common_register(
+        &mut self,
+        _register: CommonHoldingRegisters,
+    ) -> Result<u16, Self::Error>
+    where
+        Self::Error: From<SettingsError>,
+    {
+        Err(SettingsError::Unsupported.into())
+    }
+    #[allow(async_fn_in_trait)]
+    async fn read_uart_parameters(&mut self) -> Result<UartParameters, Self::Error>
+    where
+        Self::Error: From<SettingsError>,
+    {
+        let value = self
+            .read_common_register(CommonHoldingRegisters::UartParameters)
+            .await?;
+        UartParameters::from_register(value)
+            .ok_or_else(|| SettingsError::InvalidUartParameters(value).into())
+    }
+    #[allow(async_fn_in_trait)]
+    async fn read_device_address(&mut self) -> Result<u8, Self::Error>
+    where
+        Self::Error: From<SettingsError>,
+    {
+        Ok(self
+            .read_common_register(CommonHoldingRegisters::DeviceAddress)
+            .await? as u8)
+    }
+    /// Points the driver at a new unit id once the device has been moved there.
+    fn set_unit_id(&mut self, _unit_id: u8) {}
+
+    /// Writes `settings` to this unit, typically a replacement, and reads the
+    /// address back.
+    ///
+    /// The driver follows the restored address. The UART parameters are written
+    /// last, reconnect with them once the restore has finished.
+    #[allow(async_fn_in_trait)]
+    async fn restore_settings(&mut self, settings: &DeviceSettings) -> Result<(), Self::Error>
+    where
+        Self::Error: From<SettingsError>,
+    {
+        if self.read_device_address().await? != settings.address {
+            self.set_device_address(settings.address).await?;
+            self.set_unit_id(settings.address);
+            let address = self.read_device_address().await?;
+            if address != settings.address {
+                return Err(SettingsError::VerifyFailed(format!(
+                    "address read back as {}",
+                    address
+                ))
+                .into());
+            }
+        }
+        // The module may switch baud rate as soon as this is written, so it is
+        // written last and not read back
+        if self.read_uart_parameters().await? != settings.uart {
+            self.set_uart_parameters(settings.uart.baudrate, settings.uart.parity)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Modules with a control mode register per channel.
+pub(crate) trait ControlModes: WaveshareModbus {
+    type Mode: Copy + PartialEq + fmt::Debug;
+
+    async fn write_mode(&mut self, channel: Channel, mode: Self::Mode) -> Result<(), Self::Error>;
+    async fn read_modes(&mut self) -> Result<Vec<Self::Mode>, Self::Error>;
+
+    /// Writes `modes` from channel 0 up and reads them back. An empty list
+    /// leaves the modes alone, for boards without control mode registers.
+    async fn restore_control_modes(&mut self, modes: &[Self::Mode]) -> Result<(), Self::Error>
+    where
+        Self::Error: From<SettingsError>,
+    {
+        if modes.is_empty() {
+            return Ok(());
+        }
+        for (channel, mode) in Channel::ALL.into_iter().zip(modes) {
+            self.write_mode(channel, *mode).await?;
+        }
+        let read_back = self.read_modes().await?;
+        if read_back != modes {
+            return Err(SettingsError::VerifyFailed(format!(
+                "control modes read back as {:?}",
+                read_back
+            ))
+            .into());
+        }
+        Ok(())
+    }
 }
 /*
 macro_rules! impl_waveshare_modbus {
@@ -0,0 +1,297 @@
+use crate::{
+    common::Channel,
+    digital::{DigitalIO, DigitalIOError},
+    edges::{Edge, EdgeDetector, InputEvent},
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+
+#[derive(Error, Debug)]
+pub enum CounterError {
+    #[error("Parse Error: `{0}`")]
+    Parse(String),
+    #[error("IO Error: `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("Digital IO Error: `{0}`")]
+    DigitalIO(#[from] DigitalIOError),
+    #[error("Poll period must be greater than zero")]
+    InvalidPollPeriod,
+}
+
+/// Persisted pulse totals, one `channel total` line per channel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Totals(pub BTreeMap<u16, u64>);
+
+impl Totals {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CounterError> {
+        // Write then rename so a crash never leaves a truncated file behind
+        let path = path.as_ref();
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, self.to_string())?;
+        std::fs::rename(temporary, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CounterError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+}
+
+impl fmt::Display for Totals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (channel, total) in &self.0 {
+            writeln!(f, "{} {}", channel, total)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Totals {
+    type Err = CounterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut totals = BTreeMap::new();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || CounterError::Parse(line.to_string());
+            let (channel, total) = line.split_once(' ').ok_or_else(invalid)?;
+            let channel = channel.trim().parse().map_err(|_| invalid())?;
+            let total = total.trim().parse().map_err(|_| invalid())?;
+            totals.insert(channel, total);
+        }
+        Ok(Totals(totals))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ChannelCount {
+    pub total: u64,
+    // Pulses per second over the rate window
+    pub frequency: f64,
+    // Pulsing too fast for the poll period to count reliably
+    pub undersampled: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ChannelHistory {
+    total: u64,
+    pulses: VecDeque<Instant>,
+    too_slow: bool,
+}
+
+/// Counts one edge per pulse on each input channel.
+#[derive(Debug, Clone)]
+pub struct PulseCounter {
+    edge: Edge,
+    rate_window: Duration,
+    detector: EdgeDetector,
+    channels: BTreeMap<u16, ChannelHistory>,
+    last_sample: Option<Instant>,
+}
+
+impl PulseCounter {
+    pub fn new(edge: Edge, debounce: Duration, rate_window: Duration) -> Self {
+        PulseCounter {
+            edge,
+            rate_window,
+            detector: EdgeDetector::new(debounce, None),
+            channels: BTreeMap::new(),
+            last_sample: None,
+        }
+    }
+
+    pub fn with_totals(mut self, totals: &Totals) -> Self {
+        for (channel, total) in &totals.0 {
+            self.channels.entry(*channel).or_default().total = *total;
+        }
+        self
+    }
+
+    pub fn totals(&self) -> Totals {
+        Totals(
+            self.channels
+                .iter()
+                .map(|(channel, history)| (*channel, history.total))
+                .collect(),
+        )
+    }
+
+    pub fn reset(&mut self, channel: Channel) {
+        if let Some(history) = self.channels.get_mut(&(channel as u16)) {
+            history.total = 0;
+            history.pulses.clear();
+        }
+    }
+
+    pub fn count(&self, channel: Channel) -> ChannelCount {
+        let history = self.channels.get(&(channel as u16));
+        ChannelCount {
+            total: history.map(|history| history.total).unwrap_or_default(),
+            frequency: history.map(frequency).unwrap_or_default(),
+            undersampled: history.is_some_and(|history| history.too_slow),
+        }
+    }
+
+    pub fn counts(&self, channels: usize) -> Vec<ChannelCount> {
+        (0..channels)
+            .map(|index| match Channel::try_from(index as u8) {
+                Ok(channel) => self.count(channel),
+                Err(_) => ChannelCount {
+                    total: 0,
+                    frequency: 0.0,
+                    undersampled: false,
+                },
+            })
+            .collect()
+    }
+
+    pub fn update(&mut self, inputs: &[bool], now: Instant) {
+        let gap = self
+            .last_sample
+            .map(|last| now.saturating_duration_since(last));
+        self.last_sample = Some(now);
+
+        for event in self.detector.update(inputs, now) {
+            let InputEvent::Edge(event) = event else {
+                continue;
+            };
+            if event.edge != self.edge {
+                continue;
+            }
+            let history = self.channels.entry(event.channel as u16).or_default();
+            history.total += 1;
+            history.pulses.push_back(event.timestamp);
+        }
+
+        for (channel, history) in self.channels.iter_mut() {
+            while history
+                .pulses
+                .front()
+                .is_some_and(|pulse| now.saturating_duration_since(*pulse) > self.rate_window)
+            {
+                history.pulses.pop_front();
+            }
+            // A pulse has to be sampled both high and low to be seen, so the
+            // observed frequency tops out at 1 / (2 * gap) and pulses are
+            // already being lost by then. Warn from half of that.
+            let frequency = frequency(history);
+            let too_slow = gap
+                .is_some_and(|gap| frequency > 0.0 && gap.as_secs_f64() * 4.0 * frequency >= 1.0);
+            if too_slow && !history.too_slow {
+                log::warn!(
+                    "input {} pulses at {:.2} Hz but is sampled every {:?}, pulses may be missed",
+                    channel,
+                    frequency,
+                    gap.unwrap_or_default()
+                );
+            }
+            history.too_slow = too_slow;
+        }
+    }
+}
+
+fn frequency(history: &ChannelHistory) -> f64 {
+    match (history.pulses.front(), history.pulses.back()) {
+        (Some(first), Some(last)) if history.pulses.len() > 1 => {
+            let span = last.saturating_duration_since(*first).as_secs_f64();
+            if span > 0.0 {
+                (history.pulses.len() - 1) as f64 / span
+            } else {
+                0.0
+            }
+        }
+        _ => 0.0,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CounterConfig {
+    pub poll_period: Duration,
+    pub debounce: Duration,
+    pub edge: Edge,
+    pub rate_window: Duration,
+    // Totals are loaded from here on start and saved every `persist_period`
+    pub persist_path: Option<PathBuf>,
+    pub persist_period: Duration,
+}
+
+impl Default for CounterConfig {
+    fn default() -> Self {
+        CounterConfig {
+            poll_period: Duration::from_millis(50),
+            debounce: Duration::ZERO,
+            edge: Edge::Rising,
+            rate_window: Duration::from_secs(10),
+            persist_path: None,
+            persist_period: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Counts {
+    pub channels: Vec<ChannelCount>,
+    pub timestamp: Option<Instant>,
+    pub last_error: Option<String>,
+}
+
+/// Polls the inputs of `io` and publishes pulse counts until every receiver is dropped.
+pub fn spawn<const N: usize>(
+    mut io: DigitalIO<N>,
+    config: CounterConfig,
+) -> Result<(watch::Receiver<Counts>, JoinHandle<()>), CounterError> {
+    if config.poll_period.is_zero() {
+        return Err(CounterError::InvalidPollPeriod);
+    }
+    let totals = match &config.persist_path {
+        Some(path) if path.exists() => Totals::load(path)?,
+        _ => Totals::default(),
+    };
+    let mut counter =
+        PulseCounter::new(config.edge, config.debounce, config.rate_window).with_totals(&totals);
+    let (sender, receiver) = watch::channel(Counts::default());
+
+    let task = tokio::spawn(async move {
+        let persist = |counter: &PulseCounter| {
+            if let Some(path) = &config.persist_path {
+                if let Err(err) = counter.totals().save(path) {
+                    log::warn!("failed to save pulse totals to {:?}: {}", path, err);
+                }
+            }
+        };
+        let mut interval = tokio::time::interval(config.poll_period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last_persist = Instant::now();
+
+        while !sender.is_closed() {
+            interval.tick().await;
+            match io.read_input_channels().await {
+                Ok(inputs) => {
+                    let now = Instant::now();
+                    counter.update(&inputs, now);
+                    sender.send_replace(Counts {
+                        channels: counter.counts(inputs.len()),
+                        timestamp: Some(now),
+                        last_error: None,
+                    });
+                }
+                Err(err) => sender.send_modify(|counts| counts.last_error = Some(err.to_string())),
+            }
+            if last_persist.elapsed() >= config.persist_period {
+                persist(&counter);
+                last_persist = Instant::now();
+            }
+        }
+        persist(&counter);
+    });
+    Ok((receiver, task))
+}
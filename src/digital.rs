@@ -1,15 +1,32 @@
 use crate::{
-    common::{Baudrates, Channel, CommonHoldingRegisters, Parity, WaveshareModbus},
+    common::{
+        Baudrates, Channel, CommonHoldingRegisters, ControlModes, DeviceSettings, Parity,
+        SettingsError, WaveshareModbus,
+    },
     ThreadSafeContext,
 };
+use std::fmt;
+use std::ops::{
+    BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Index, IndexMut, Not,
+};
+use std::str::FromStr;
+use std::time::Duration;
 use thiserror::Error;
+use tokio_modbus::Slave;
+
+// The flash registers count in units of 100ms
+pub const FLASH_INTERVAL_UNIT: Duration = Duration::from_millis(100);
 
+/// A Waveshare relay or IO board with `N` outputs and inputs.
 #[derive(Debug)]
-pub struct DigitalIO {
+pub struct DigitalIO<const N: usize = 8> {
     pub unit_id: u8,
     pub context: ThreadSafeContext,
 }
 
+pub type DigitalIO16 = DigitalIO<16>;
+pub type DigitalIO32 = DigitalIO<32>;
+
 #[derive(Error, Debug)]
 pub enum DigitalIOError {
     #[error("Modbus Exception Error: `{0}`")]
@@ -18,78 +35,281 @@ pub enum DigitalIOError {
     ModbusError(tokio_modbus::Error),
     #[error("Invalid Control Mode")]
     InvalidControlMode,
+    #[error("Invalid Flash Interval: `{0:?}`")]
+    InvalidFlashInterval(Duration),
+    #[error("Invalid Channel: `{0:?}`")]
+    InvalidChannel(Channel),
+    #[error("Settings Error: `{0}`")]
+    Settings(#[from] SettingsError),
 }
 
-#[derive(Debug, Copy, Clone)]
-pub struct IoBank {
-    pub ch0: bool,
-    pub ch1: bool,
-    pub ch2: bool,
-    pub ch3: bool,
-    pub ch4: bool,
-    pub ch5: bool,
-    pub ch6: bool,
-    pub ch7: bool,
-}
-
-impl IoBank {
-    pub fn as_action_array(&self) -> [Action; 8] {
-        [
-            if self.ch0 { Action::On } else { Action::Off },
-            if self.ch1 { Action::On } else { Action::Off },
-            if self.ch2 { Action::On } else { Action::Off },
-            if self.ch3 { Action::On } else { Action::Off },
-            if self.ch4 { Action::On } else { Action::Off },
-            if self.ch5 { Action::On } else { Action::Off },
-            if self.ch6 { Action::On } else { Action::Off },
-            if self.ch7 { Action::On } else { Action::Off },
-        ]
-    }
-}
-
-impl From<u8> for IoBank {
+pub fn flash_interval_units(interval: Duration) -> Result<u16, DigitalIOError> {
+    let unit = FLASH_INTERVAL_UNIT.as_millis();
+    let millis = interval.as_millis();
+    if millis == 0 || !millis.is_multiple_of(unit) {
+        return Err(DigitalIOError::InvalidFlashInterval(interval));
+    }
+    u16::try_from(millis / unit).map_err(|_| DigitalIOError::InvalidFlashInterval(interval))
+}
+
+/// One flag per channel, for boards with up to 32 channels.
+///
+/// Coils are packed least significant bit first, channel 0 is bit 0 of the first byte.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct IoBank<const N: usize = 8> {
+    channels: [bool; N],
+}
+
+impl<const N: usize> IoBank<N> {
+    // Fails to compile for banks wider than 32 channels
+    pub const CHANNELS: [Channel; N] = {
+        let mut channels = [Channel::Channel0; N];
+        let mut index = 0;
+        while index < N {
+            channels[index] = Channel::ALL[index];
+            index += 1;
+        }
+        channels
+    };
+
+    /// Channels beyond this bank read as off, unlike indexing which panics.
+    pub fn get(&self, channel: Channel) -> bool {
+        self.channels
+            .get(channel as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Panics if `channel` is not on this bank, like indexing.
+    pub fn set(&mut self, channel: Channel, value: bool) {
+        self[channel] = value;
+    }
+
+    pub fn count(&self) -> u32 {
+        self.channels.iter().filter(|value| **value).count() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.channels.contains(&true)
+    }
+
+    /// Channels that are set, in channel order.
+    pub fn iter(&self) -> impl Iterator<Item = Channel> {
+        let bank = *self;
+        Self::CHANNELS
+            .into_iter()
+            .filter(move |channel| bank[*channel])
+    }
+
+    /// Channels whose state differs between the two banks.
+    pub fn changed(&self, other: &IoBank<N>) -> impl Iterator<Item = Channel> {
+        (*self ^ *other).iter()
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.iter()
+            .fold(0, |bits, channel| bits | 1 << channel as u32)
+    }
+
+    // Bits above the width of the bank are ignored
+    pub fn from_bits(bits: u32) -> Self {
+        IoBank {
+            channels: std::array::from_fn(|index| bits & (1 << index) != 0),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let bits = self.bits().to_le_bytes();
+        bits[..N.div_ceil(8)].to_vec()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut bits = [0u8; 4];
+        for (bits, byte) in bits.iter_mut().zip(bytes) {
+            *bits = *byte;
+        }
+        IoBank::from_bits(u32::from_le_bytes(bits))
+    }
+
+    pub fn as_action_array(&self) -> [Action; N] {
+        self.channels
+            .map(|value| if value { Action::On } else { Action::Off })
+    }
+}
+
+impl<const N: usize> Default for IoBank<N> {
+    fn default() -> Self {
+        IoBank {
+            channels: [false; N],
+        }
+    }
+}
+
+impl From<u8> for IoBank<8> {
     fn from(data: u8) -> Self {
-        Self {
-            ch0: data & 0x01 > 0,
-            ch1: data & 0x02 > 0,
-            ch2: data & 0x04 > 0,
-            ch3: data & 0x08 > 0,
-            ch4: data & 0x10 > 0,
-            ch5: data & 0x20 > 0,
-            ch6: data & 0x40 > 0,
-            ch7: data & 0x80 > 0,
+        IoBank::from_bits(data as u32)
+    }
+}
+
+impl From<IoBank<8>> for u8 {
+    fn from(bank: IoBank<8>) -> Self {
+        bank.bits() as u8
+    }
+}
+
+impl From<u16> for IoBank<16> {
+    fn from(data: u16) -> Self {
+        IoBank::from_bits(data as u32)
+    }
+}
+
+impl From<IoBank<16>> for u16 {
+    fn from(bank: IoBank<16>) -> Self {
+        bank.bits() as u16
+    }
+}
+
+impl From<u32> for IoBank<32> {
+    fn from(data: u32) -> Self {
+        IoBank::from_bits(data)
+    }
+}
+
+impl From<IoBank<32>> for u32 {
+    fn from(bank: IoBank<32>) -> Self {
+        bank.bits()
+    }
+}
+
+// Missing channels read as off, extra values are ignored
+impl<const N: usize> From<Vec<bool>> for IoBank<N> {
+    fn from(values: Vec<bool>) -> Self {
+        IoBank::from(values.as_slice())
+    }
+}
+
+impl<const N: usize> From<&[bool]> for IoBank<N> {
+    fn from(values: &[bool]) -> Self {
+        IoBank {
+            channels: std::array::from_fn(|index| values.get(index).copied().unwrap_or(false)),
         }
     }
 }
 
-impl Into<u8> for IoBank {
-    fn into(self) -> u8 {
-        let mut out = 0u8;
-        if self.ch0 {
-            out |= 0x01
-        };
-        if self.ch1 {
-            out |= 0x02
-        };
-        if self.ch2 {
-            out |= 0x04
-        };
-        if self.ch3 {
-            out |= 0x08
-        };
-        if self.ch4 {
-            out |= 0x10
-        };
-        if self.ch5 {
-            out |= 0x20
-        };
-        if self.ch6 {
-            out |= 0x40
-        };
-        if self.ch7 {
-            out |= 0x80
-        };
-        out
+impl<const N: usize> From<[bool; N]> for IoBank<N> {
+    fn from(channels: [bool; N]) -> Self {
+        IoBank { channels }
+    }
+}
+
+impl<const N: usize> From<IoBank<N>> for [bool; N] {
+    fn from(bank: IoBank<N>) -> Self {
+        bank.channels
+    }
+}
+
+/// Panics if `channel` is not on this bank, e.g. `Channel8` on an `IoBank<8>`.
+impl<const N: usize> Index<Channel> for IoBank<N> {
+    type Output = bool;
+
+    fn index(&self, channel: Channel) -> &bool {
+        &self.channels[channel as usize]
+    }
+}
+
+impl<const N: usize> IndexMut<Channel> for IoBank<N> {
+    fn index_mut(&mut self, channel: Channel) -> &mut bool {
+        &mut self.channels[channel as usize]
+    }
+}
+
+impl<const N: usize> BitAnd for IoBank<N> {
+    type Output = IoBank<N>;
+
+    fn bitand(self, other: IoBank<N>) -> IoBank<N> {
+        IoBank {
+            channels: std::array::from_fn(|index| self.channels[index] & other.channels[index]),
+        }
+    }
+}
+
+impl<const N: usize> BitOr for IoBank<N> {
+    type Output = IoBank<N>;
+
+    fn bitor(self, other: IoBank<N>) -> IoBank<N> {
+        IoBank {
+            channels: std::array::from_fn(|index| self.channels[index] | other.channels[index]),
+        }
+    }
+}
+
+impl<const N: usize> BitXor for IoBank<N> {
+    type Output = IoBank<N>;
+
+    fn bitxor(self, other: IoBank<N>) -> IoBank<N> {
+        IoBank {
+            channels: std::array::from_fn(|index| self.channels[index] ^ other.channels[index]),
+        }
+    }
+}
+
+impl<const N: usize> Not for IoBank<N> {
+    type Output = IoBank<N>;
+
+    fn not(self) -> IoBank<N> {
+        IoBank {
+            channels: self.channels.map(|value| !value),
+        }
+    }
+}
+
+impl<const N: usize> BitAndAssign for IoBank<N> {
+    fn bitand_assign(&mut self, other: IoBank<N>) {
+        *self = *self & other;
+    }
+}
+
+impl<const N: usize> BitOrAssign for IoBank<N> {
+    fn bitor_assign(&mut self, other: IoBank<N>) {
+        *self = *self | other;
+    }
+}
+
+impl<const N: usize> BitXorAssign for IoBank<N> {
+    fn bitxor_assign(&mut self, other: IoBank<N>) {
+        *self = *self ^ other;
+    }
+}
+
+// Serialised as one bool per channel
+#[cfg(feature = "serde")]
+impl<const N: usize> serde::Serialize for IoBank<N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.channels.as_slice().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const N: usize> serde::Deserialize<'de> for IoBank<N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let channels = Vec::<bool>::deserialize(deserializer)?;
+        if channels.len() != N {
+            return Err(serde::de::Error::invalid_length(
+                channels.len(),
+                &format!("{} channels", N).as_str(),
+            ));
+        }
+        Ok(IoBank::from(channels))
+    }
+}
+
+impl<const N: usize> fmt::Display for IoBank<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0b")?;
+        for value in self.channels.iter().rev() {
+            write!(f, "{}", if *value { '1' } else { '0' })?;
+        }
+        Ok(())
     }
 }
 
@@ -115,6 +335,11 @@ pub enum HoldingRegisterBases {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 #[repr(u16)]
 pub enum Action {
     On = 0xFF00,
@@ -122,6 +347,11 @@ pub enum Action {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 #[repr(u16)]
 pub enum ControlMode {
     Command = 0x0000,
@@ -129,15 +359,71 @@ pub enum ControlMode {
     Flip = 0x0002,
 }
 
+impl ControlMode {
+    pub fn from_u16(value: u16) -> Result<ControlMode, DigitalIOError> {
+        match value {
+            0x0000 => Ok(ControlMode::Command),
+            0x0001 => Ok(ControlMode::Linked),
+            0x0002 => Ok(ControlMode::Flip),
+            _ => Err(DigitalIOError::InvalidControlMode),
+        }
+    }
+}
+
+impl fmt::Display for ControlMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlMode::Command => write!(f, "command"),
+            ControlMode::Linked => write!(f, "linked"),
+            ControlMode::Flip => write!(f, "flip"),
+        }
+    }
+}
+
+impl FromStr for ControlMode {
+    type Err = DigitalIOError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "command" => Ok(ControlMode::Command),
+            "linked" => Ok(ControlMode::Linked),
+            "flip" => Ok(ControlMode::Flip),
+            _ => Err(DigitalIOError::InvalidControlMode),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DigitalIOSnapshot {
+    pub settings: DeviceSettings,
+    // Empty for boards without control mode registers
+    pub control_modes: Vec<ControlMode>,
+}
+
 impl DigitalIO {
     pub fn new(unit_id: u8, context: ThreadSafeContext) -> Self {
         DigitalIO { unit_id, context }
     }
+}
+
+impl<const N: usize> DigitalIO<N> {
+    pub fn with_channels(unit_id: u8, context: ThreadSafeContext) -> Self {
+        DigitalIO { unit_id, context }
+    }
+
+    pub fn channels(&self) -> usize {
+        N
+    }
+
+    fn address(channel: Channel) -> Result<u16, DigitalIOError> {
+        if channel as usize >= N {
+            return Err(DigitalIOError::InvalidChannel(channel));
+        }
+        Ok(channel as u16)
+    }
 
     pub async fn set_slave_id(&mut self) {
-        self.context
-            .set_slave(tokio_modbus::Slave(self.unit_id))
-            .await;
+        self.context.set_slave(Slave(self.unit_id)).await;
     }
 
     pub async fn write_output_channel(
@@ -145,10 +431,12 @@ impl DigitalIO {
         channel: Channel,
         action: Action,
     ) -> Result<(), DigitalIOError> {
-        self.set_slave_id().await;
+        let address = Self::address(channel)?;
         self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_coil(
-                channel as u16 + OutputRegisterBases::OutputChannel as u16,
+                address + OutputRegisterBases::OutputChannel as u16,
                 if action == Action::On { true } else { false },
             )
             .await
@@ -158,8 +446,9 @@ impl DigitalIO {
     }
 
     pub async fn open_all_outputs(&mut self) -> Result<(), DigitalIOError> {
-        self.set_slave_id().await;
         self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_coil(OutputRegisterBases::ControlAllRegisters as u16, true)
             .await
             .map_err(DigitalIOError::ModbusError)?
@@ -168,8 +457,9 @@ impl DigitalIO {
     }
 
     pub async fn close_all_outputs(&mut self) -> Result<(), DigitalIOError> {
-        self.set_slave_id().await;
         self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_coil(OutputRegisterBases::ControlAllRegisters as u16, false)
             .await
             .map_err(DigitalIOError::ModbusError)?
@@ -179,11 +469,12 @@ impl DigitalIO {
 
     pub async fn write_output_channels(
         &mut self,
-        actions: [Action; 8],
+        actions: [Action; N],
     ) -> Result<(), DigitalIOError> {
         let values = actions.map(|x| x == Action::On);
-        self.set_slave_id().await;
         self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_multiple_coils(OutputRegisterBases::OutputChannel as u16, &values)
             .await
             .map_err(DigitalIOError::ModbusError)?
@@ -191,15 +482,58 @@ impl DigitalIO {
         Ok(())
     }
 
+    /// Sets the channels in `set` and clears the channels in `clear` without
+    /// touching the others, returning the bank before and after the change.
+    ///
+    /// The bus is held from the read to the write, a channel in both masks is set.
+    pub async fn update_output_channels(
+        &mut self,
+        set: IoBank<N>,
+        clear: IoBank<N>,
+    ) -> Result<(IoBank<N>, IoBank<N>), DigitalIOError> {
+        self.modify_output_channels(|bank| (bank & !clear) | set)
+            .await
+    }
+
+    /// Reads the outputs and writes back `modify(outputs)` while holding the bus.
+    pub async fn modify_output_channels(
+        &mut self,
+        modify: impl FnOnce(IoBank<N>) -> IoBank<N>,
+    ) -> Result<(IoBank<N>, IoBank<N>), DigitalIOError> {
+        let mut context = self.context.lock_slave(Slave(self.unit_id)).await;
+        let before = IoBank::from(
+            context
+                .read_coils(OutputRegisterBases::OutputChannel as u16, N as u16)
+                .await
+                .map_err(DigitalIOError::ModbusError)?
+                .map_err(DigitalIOError::ModbusException)?,
+        );
+        let after = modify(before);
+        if after != before {
+            context
+                .write_multiple_coils(
+                    OutputRegisterBases::OutputChannel as u16,
+                    &<[bool; N]>::from(after),
+                )
+                .await
+                .map_err(DigitalIOError::ModbusError)?
+                .map_err(DigitalIOError::ModbusException)?;
+        }
+        Ok((before, after))
+    }
+
     pub async fn flash_output_on(
         &mut self,
         channel: Channel,
-        interval: u16,
+        interval: Duration,
     ) -> Result<(), DigitalIOError> {
-        self.set_slave_id().await;
+        let interval = flash_interval_units(interval)?;
+        let address = Self::address(channel)?;
         self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_register(
-                channel as u16 + OutputRegisterBases::OutputChannelFlashOn as u16,
+                address + OutputRegisterBases::OutputChannelFlashOn as u16,
                 interval,
             )
             .await
@@ -211,12 +545,15 @@ impl DigitalIO {
     pub async fn flash_output_off(
         &mut self,
         channel: Channel,
-        interval: u16,
+        interval: Duration,
     ) -> Result<(), DigitalIOError> {
-        self.set_slave_id().await;
+        let interval = flash_interval_units(interval)?;
+        let address = Self::address(channel)?;
         self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_register(
-                channel as u16 + OutputRegisterBases::OutputChannelFlashOff as u16,
+                address + OutputRegisterBases::OutputChannelFlashOff as u16,
                 interval,
             )
             .await
@@ -225,14 +562,51 @@ impl DigitalIO {
         Ok(())
     }
 
+    pub async fn read_output_channel_status(
+        &mut self,
+        channel: Channel,
+    ) -> Result<bool, DigitalIOError> {
+        let address = Self::address(channel)?;
+        let result = self
+            .context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_coils(address + OutputRegisterBases::OutputChannel as u16, 1)
+            .await
+            .map_err(DigitalIOError::ModbusError)?
+            .map_err(DigitalIOError::ModbusException)?;
+
+        Ok(result.first().copied().unwrap_or(false))
+    }
+
+    pub async fn read_output_channels(&mut self) -> Result<Vec<bool>, DigitalIOError> {
+        self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_coils(OutputRegisterBases::OutputChannel as u16, N as u16)
+            .await
+            .map_err(DigitalIOError::ModbusError)?
+            .map_err(DigitalIOError::ModbusException)
+    }
+
+    pub async fn read_output_bank(&mut self) -> Result<IoBank<N>, DigitalIOError> {
+        Ok(IoBank::from(self.read_output_channels().await?))
+    }
+
+    pub async fn write_output_bank(&mut self, bank: IoBank<N>) -> Result<(), DigitalIOError> {
+        self.write_output_channels(bank.as_action_array()).await
+    }
+
     pub async fn read_input_channel_status(
         &mut self,
         channel: Channel,
     ) -> Result<bool, DigitalIOError> {
-        self.set_slave_id().await;
+        let address = Self::address(channel)?;
         let result = self
             .context
-            .read_discrete_inputs(channel as u16 + InputRegisterBases::InputChannels as u16, 1)
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_discrete_inputs(address + InputRegisterBases::InputChannels as u16, 1)
             .await
             .map_err(DigitalIOError::ModbusError)?
             .map_err(DigitalIOError::ModbusException)?;
@@ -241,23 +615,30 @@ impl DigitalIO {
     }
 
     pub async fn read_input_channels(&mut self) -> Result<Vec<bool>, DigitalIOError> {
-        self.set_slave_id().await;
         self.context
-            .read_discrete_inputs(InputRegisterBases::InputChannels as u16, 8)
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_discrete_inputs(InputRegisterBases::InputChannels as u16, N as u16)
             .await
             .map_err(DigitalIOError::ModbusError)?
             .map_err(DigitalIOError::ModbusException)
     }
 
+    pub async fn read_input_bank(&mut self) -> Result<IoBank<N>, DigitalIOError> {
+        Ok(IoBank::from(self.read_input_channels().await?))
+    }
+
     pub async fn set_output_control_mode(
         &mut self,
         channel: Channel,
         mode: ControlMode,
     ) -> Result<(), DigitalIOError> {
-        self.set_slave_id().await;
+        let address = Self::address(channel)?;
         self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_register(
-                HoldingRegisterBases::ControlMode as u16 + channel as u16,
+                HoldingRegisterBases::ControlMode as u16 + address,
                 mode as u16,
             )
             .await
@@ -336,9 +717,48 @@ impl DigitalIO {
         ))
     }
     */
+
+    pub async fn read_output_control_modes(&mut self) -> Result<Vec<ControlMode>, DigitalIOError> {
+        self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_holding_registers(HoldingRegisterBases::ControlMode as u16, N as u16)
+            .await
+            .map_err(DigitalIOError::ModbusError)?
+            .map_err(DigitalIOError::ModbusException)?
+            .into_iter()
+            .map(ControlMode::from_u16)
+            .collect()
+    }
+
+    pub async fn snapshot(&mut self) -> Result<DigitalIOSnapshot, DigitalIOError> {
+        Ok(DigitalIOSnapshot {
+            settings: DeviceSettings::read(self).await?,
+            control_modes: self.read_output_control_modes().await?,
+        })
+    }
+
+    /// Writes and verifies the control modes if the snapshot has any, then
+    /// `restore_settings`.
+    pub async fn restore(&mut self, snapshot: &DigitalIOSnapshot) -> Result<(), DigitalIOError> {
+        self.restore_control_modes(&snapshot.control_modes).await?;
+        self.restore_settings(&snapshot.settings).await
+    }
+}
+
+impl<const N: usize> ControlModes for DigitalIO<N> {
+    type Mode = ControlMode;
+
+    async fn write_mode(&mut self, channel: Channel, mode: ControlMode) -> Result<(), Self::Error> {
+        self.set_output_control_mode(channel, mode).await
+    }
+
+    async fn read_modes(&mut self) -> Result<Vec<ControlMode>, Self::Error> {
+        self.read_output_control_modes().await
+    }
 }
 
-impl WaveshareModbus for DigitalIO {
+impl<const N: usize> WaveshareModbus for DigitalIO<N> {
     type Error = DigitalIOError;
 
     async fn set_uart_parameters(
@@ -346,10 +766,11 @@ impl WaveshareModbus for DigitalIO {
         baud: Baudrates,
         parity: Parity,
     ) -> Result<(), Self::Error> {
-        self.set_slave_id().await;
         let value = ((parity as u16) << 8) | (baud as u16);
         let result = self
             .context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_register(CommonHoldingRegisters::UartParameters as u16, value)
             .await
             .map_err(|err| DigitalIOError::ModbusError(err))?
@@ -358,9 +779,10 @@ impl WaveshareModbus for DigitalIO {
     }
 
     async fn set_device_address(&mut self, address: u8) -> Result<(), Self::Error> {
-        self.set_slave_id().await;
         let result = self
             .context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .write_single_register(CommonHoldingRegisters::DeviceAddress as u16, address as u16)
             .await
             .map_err(|err| DigitalIOError::ModbusError(err))?
@@ -369,13 +791,141 @@ impl WaveshareModbus for DigitalIO {
     }
 
     async fn read_software_version(&mut self) -> Result<u16, Self::Error> {
-        self.set_slave_id().await;
         let result = self
             .context
+            .lock_slave(Slave(self.unit_id))
+            .await
             .read_holding_registers(CommonHoldingRegisters::SoftwareVersion as u16, 1)
             .await
             .map_err(|err| DigitalIOError::ModbusError(err))?
             .map_err(|err| DigitalIOError::ModbusException(err))?;
         Ok(result[0])
     }
+
+    async fn read_common_register(
+        &mut self,
+        register: CommonHoldingRegisters,
+    ) -> Result<u16, Self::Error> {
+        let result = self
+            .context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_holding_registers(register as u16, 1)
+            .await
+            .map_err(DigitalIOError::ModbusError)?
+            .map_err(DigitalIOError::ModbusException)?;
+        Ok(result[0])
+    }
+
+    fn set_unit_id(&mut self, unit_id: u8) {
+        self.unit_id = unit_id;
+    }
+}
+
+/// Board widths for [`AnyDigitalIO`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Width {
+    #[default]
+    W8,
+    W16,
+    W32,
+}
+
+impl Width {
+    pub fn channels(&self) -> usize {
+        match self {
+            Width::W8 => 8,
+            Width::W16 => 16,
+            Width::W32 => 32,
+        }
+    }
+}
+
+/// A digital board whose width is only known at runtime.
+#[derive(Debug)]
+pub enum AnyDigitalIO {
+    W8(DigitalIO),
+    W16(DigitalIO16),
+    W32(DigitalIO32),
+}
+
+impl AnyDigitalIO {
+    pub fn new(unit_id: u8, context: ThreadSafeContext, width: Width) -> Self {
+        match width {
+            Width::W8 => AnyDigitalIO::W8(DigitalIO::with_channels(unit_id, context)),
+            Width::W16 => AnyDigitalIO::W16(DigitalIO::with_channels(unit_id, context)),
+            Width::W32 => AnyDigitalIO::W32(DigitalIO::with_channels(unit_id, context)),
+        }
+    }
+
+    pub fn width(&self) -> Width {
+        match self {
+            AnyDigitalIO::W8(_) => Width::W8,
+            AnyDigitalIO::W16(_) => Width::W16,
+            AnyDigitalIO::W32(_) => Width::W32,
+        }
+    }
+
+    pub async fn write_output_channel(
+        &mut self,
+        channel: Channel,
+        action: Action,
+    ) -> Result<(), DigitalIOError> {
+        match self {
+            AnyDigitalIO::W8(io) => io.write_output_channel(channel, action).await,
+            AnyDigitalIO::W16(io) => io.write_output_channel(channel, action).await,
+            AnyDigitalIO::W32(io) => io.write_output_channel(channel, action).await,
+        }
+    }
+
+    pub async fn close_all_outputs(&mut self) -> Result<(), DigitalIOError> {
+        match self {
+            AnyDigitalIO::W8(io) => io.close_all_outputs().await,
+            AnyDigitalIO::W16(io) => io.close_all_outputs().await,
+            AnyDigitalIO::W32(io) => io.close_all_outputs().await,
+        }
+    }
+
+    pub async fn read_output_channels(&mut self) -> Result<Vec<bool>, DigitalIOError> {
+        match self {
+            AnyDigitalIO::W8(io) => io.read_output_channels().await,
+            AnyDigitalIO::W16(io) => io.read_output_channels().await,
+            AnyDigitalIO::W32(io) => io.read_output_channels().await,
+        }
+    }
+
+    // Bits above the width of the board are ignored
+    pub async fn write_output_bank(&mut self, bank: IoBank<32>) -> Result<(), DigitalIOError> {
+        match self {
+            AnyDigitalIO::W8(io) => io.write_output_bank(IoBank::from_bits(bank.bits())).await,
+            AnyDigitalIO::W16(io) => io.write_output_bank(IoBank::from_bits(bank.bits())).await,
+            AnyDigitalIO::W32(io) => io.write_output_bank(bank).await,
+        }
+    }
+
+    pub async fn read_input_channels(&mut self) -> Result<Vec<bool>, DigitalIOError> {
+        match self {
+            AnyDigitalIO::W8(io) => io.read_input_channels().await,
+            AnyDigitalIO::W16(io) => io.read_input_channels().await,
+            AnyDigitalIO::W32(io) => io.read_input_channels().await,
+        }
+    }
+}
+
+impl From<DigitalIO> for AnyDigitalIO {
+    fn from(io: DigitalIO) -> Self {
+        AnyDigitalIO::W8(io)
+    }
+}
+
+impl From<DigitalIO16> for AnyDigitalIO {
+    fn from(io: DigitalIO16) -> Self {
+        AnyDigitalIO::W16(io)
+    }
+}
+
+impl From<DigitalIO32> for AnyDigitalIO {
+    fn from(io: DigitalIO32) -> Self {
+        AnyDigitalIO::W32(io)
+    }
 }
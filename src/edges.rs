@@ -0,0 +1,171 @@
+use crate::{
+    common::Channel,
+    digital::{DigitalIO, DigitalIOError},
+};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+
+#[derive(Error, Debug)]
+pub enum EdgeError {
+    #[error("Poll period must be greater than zero")]
+    InvalidPollPeriod,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EdgeEvent {
+    pub channel: Channel,
+    pub edge: Edge,
+    pub timestamp: Instant,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+    Edge(EdgeEvent),
+    // The gap between two samples was longer than the shortest expected pulse
+    MissedTransitions { gap: Duration, timestamp: Instant },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct EdgeConfig {
+    pub poll_period: Duration,
+    pub debounce: Duration,
+    pub min_pulse_width: Option<Duration>,
+}
+
+impl Default for EdgeConfig {
+    fn default() -> Self {
+        EdgeConfig {
+            poll_period: Duration::from_millis(100),
+            debounce: Duration::ZERO,
+            min_pulse_width: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct ChannelState {
+    stable: bool,
+    pending: Option<(bool, Instant)>,
+}
+
+/// Turns successive input samples into debounced edge events.
+#[derive(Debug, Clone)]
+pub struct EdgeDetector {
+    debounce: Duration,
+    min_pulse_width: Option<Duration>,
+    channels: Vec<ChannelState>,
+    last_sample: Option<Instant>,
+}
+
+impl EdgeDetector {
+    pub fn new(debounce: Duration, min_pulse_width: Option<Duration>) -> Self {
+        EdgeDetector {
+            debounce,
+            min_pulse_width,
+            channels: Vec::new(),
+            last_sample: None,
+        }
+    }
+
+    pub fn states(&self) -> Vec<bool> {
+        self.channels.iter().map(|state| state.stable).collect()
+    }
+
+    pub fn update(&mut self, inputs: &[bool], now: Instant) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        if let (Some(last), Some(min_pulse_width)) = (self.last_sample, self.min_pulse_width) {
+            let gap = now.saturating_duration_since(last);
+            if gap > min_pulse_width {
+                events.push(InputEvent::MissedTransitions {
+                    gap,
+                    timestamp: now,
+                });
+            }
+        }
+        self.last_sample = Some(now);
+
+        // The first sample only establishes the baseline
+        if self.channels.len() != inputs.len() {
+            self.channels = inputs
+                .iter()
+                .map(|value| ChannelState {
+                    stable: *value,
+                    pending: None,
+                })
+                .collect();
+            return events;
+        }
+
+        for (index, (state, value)) in self.channels.iter_mut().zip(inputs).enumerate() {
+            if *value == state.stable {
+                // Changed back before the debounce time elapsed
+                state.pending = None;
+                continue;
+            }
+            let since = match state.pending {
+                Some((pending, since)) if pending == *value => since,
+                _ => {
+                    state.pending = Some((*value, now));
+                    now
+                }
+            };
+            if now.saturating_duration_since(since) < self.debounce {
+                continue;
+            }
+            state.stable = *value;
+            state.pending = None;
+            let Ok(channel) = Channel::try_from(index as u8) else {
+                continue;
+            };
+            events.push(InputEvent::Edge(EdgeEvent {
+                channel,
+                edge: if *value { Edge::Rising } else { Edge::Falling },
+                timestamp: since,
+            }));
+        }
+        events
+    }
+}
+
+/// Polls the inputs of `io` and yields change-of-state events until the stream is dropped.
+pub fn edge_stream<const N: usize>(
+    mut io: DigitalIO<N>,
+    config: EdgeConfig,
+) -> Result<impl Stream<Item = Result<InputEvent, DigitalIOError>>, EdgeError> {
+    if config.poll_period.is_zero() {
+        return Err(EdgeError::InvalidPollPeriod);
+    }
+    let (sender, receiver) = mpsc::channel(64);
+    tokio::spawn(async move {
+        let mut detector = EdgeDetector::new(config.debounce, config.min_pulse_width);
+        let mut interval = tokio::time::interval(config.poll_period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        while !sender.is_closed() {
+            interval.tick().await;
+            match io.read_input_channels().await {
+                Ok(inputs) => {
+                    for event in detector.update(&inputs, Instant::now()) {
+                        if sender.send(Ok(event)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(err) => {
+                    if sender.send(Err(err)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+    Ok(ReceiverStream::new(receiver))
+}
@@ -0,0 +1,316 @@
+use crate::{
+    analog_in::{AnalogInput, AnalogInputError},
+    common::Channel,
+};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::str::FromStr;
+use thiserror::Error;
+use tokio::time::Instant;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FilterError {
+    #[error("Invalid Filter Spec: `{0}`")]
+    InvalidSpec(String),
+    #[error("Window must hold at least one sample")]
+    InvalidWindow,
+    #[error("Smoothing factor must be greater than zero and at most one")]
+    InvalidAlpha,
+    #[error("Rate limit must be finite and greater than zero")]
+    InvalidRate,
+}
+
+pub trait Filter: Debug + Send {
+    fn apply(&mut self, value: f64, now: Instant) -> f64;
+    fn reset(&mut self);
+}
+
+#[derive(Debug, Clone)]
+pub struct MovingAverage {
+    window: usize,
+    values: VecDeque<f64>,
+    sum: f64,
+}
+
+impl MovingAverage {
+    pub fn new(window: usize) -> Result<Self, FilterError> {
+        if window == 0 {
+            return Err(FilterError::InvalidWindow);
+        }
+        Ok(MovingAverage {
+            window,
+            values: VecDeque::new(),
+            sum: 0.0,
+        })
+    }
+}
+
+impl Filter for MovingAverage {
+    fn apply(&mut self, value: f64, _now: Instant) -> f64 {
+        self.values.push_back(value);
+        self.sum += value;
+        if self.values.len() > self.window {
+            self.sum -= self.values.pop_front().unwrap_or_default();
+        }
+        self.sum / self.values.len() as f64
+    }
+
+    fn reset(&mut self) {
+        self.values.clear();
+        self.sum = 0.0;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExponentialSmoothing {
+    alpha: f64,
+    state: Option<f64>,
+}
+
+impl ExponentialSmoothing {
+    // alpha of 1.0 passes values through unchanged, smaller values smooth harder
+    pub fn new(alpha: f64) -> Result<Self, FilterError> {
+        if !(alpha > 0.0 && alpha <= 1.0) {
+            return Err(FilterError::InvalidAlpha);
+        }
+        Ok(ExponentialSmoothing { alpha, state: None })
+    }
+}
+
+impl Filter for ExponentialSmoothing {
+    fn apply(&mut self, value: f64, _now: Instant) -> f64 {
+        let next = match self.state {
+            Some(state) => state + self.alpha * (value - state),
+            None => value,
+        };
+        self.state = Some(next);
+        next
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Median {
+    window: usize,
+    values: VecDeque<f64>,
+}
+
+impl Median {
+    pub fn new(window: usize) -> Result<Self, FilterError> {
+        if window == 0 {
+            return Err(FilterError::InvalidWindow);
+        }
+        Ok(Median {
+            window,
+            values: VecDeque::new(),
+        })
+    }
+}
+
+impl Filter for Median {
+    fn apply(&mut self, value: f64, _now: Instant) -> f64 {
+        self.values.push_back(value);
+        if self.values.len() > self.window {
+            self.values.pop_front();
+        }
+        let mut sorted: Vec<f64> = self.values.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        let middle = sorted.len() / 2;
+        if sorted.len() % 2 == 1 {
+            sorted[middle]
+        } else {
+            (sorted[middle - 1] + sorted[middle]) / 2.0
+        }
+    }
+
+    fn reset(&mut self) {
+        self.values.clear();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    max_per_second: f64,
+    last: Option<(f64, Instant)>,
+}
+
+impl RateLimit {
+    pub fn new(max_per_second: f64) -> Result<Self, FilterError> {
+        if !(max_per_second.is_finite() && max_per_second > 0.0) {
+            return Err(FilterError::InvalidRate);
+        }
+        Ok(RateLimit {
+            max_per_second,
+            last: None,
+        })
+    }
+}
+
+impl Filter for RateLimit {
+    fn apply(&mut self, value: f64, now: Instant) -> f64 {
+        let next = match self.last {
+            Some((last, at)) => {
+                let max_step =
+                    self.max_per_second * now.saturating_duration_since(at).as_secs_f64();
+                last + (value - last).clamp(-max_step, max_step)
+            }
+            None => value,
+        };
+        self.last = Some((next, now));
+        next
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// Describes a filter so it can be built from configuration.
+///
+/// The string form is `<kind>:<parameter>`, e.g. `median:5` or `ema:0.2`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FilterSpec {
+    MovingAverage(usize),
+    ExponentialSmoothing(f64),
+    Median(usize),
+    RateLimit(f64),
+}
+
+impl FilterSpec {
+    pub fn build(&self) -> Result<Box<dyn Filter>, FilterError> {
+        Ok(match *self {
+            FilterSpec::MovingAverage(window) => Box::new(MovingAverage::new(window)?),
+            FilterSpec::ExponentialSmoothing(alpha) => Box::new(ExponentialSmoothing::new(alpha)?),
+            FilterSpec::Median(window) => Box::new(Median::new(window)?),
+            FilterSpec::RateLimit(max_per_second) => Box::new(RateLimit::new(max_per_second)?),
+        })
+    }
+}
+
+impl FromStr for FilterSpec {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FilterError::InvalidSpec(s.to_string());
+        let (kind, parameter) = s.trim().split_once(':').ok_or_else(invalid)?;
+        let parameter = parameter.trim();
+        let spec = match kind.trim() {
+            "moving_average" => Ok(FilterSpec::MovingAverage(
+                parameter.parse().map_err(|_| invalid())?,
+            )),
+            "ema" => Ok(FilterSpec::ExponentialSmoothing(
+                parameter.parse().map_err(|_| invalid())?,
+            )),
+            "median" => Ok(FilterSpec::Median(
+                parameter.parse().map_err(|_| invalid())?,
+            )),
+            "rate_limit" => Ok(FilterSpec::RateLimit(
+                parameter.parse().map_err(|_| invalid())?,
+            )),
+            _ => Err(invalid()),
+        }?;
+        // Building checks the parameter is in range
+        spec.build()?;
+        Ok(spec)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn Filter>>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        FilterChain::default()
+    }
+
+    pub fn from_specs(specs: &[FilterSpec]) -> Result<Self, FilterError> {
+        Ok(FilterChain {
+            filters: specs
+                .iter()
+                .map(FilterSpec::build)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn push(&mut self, filter: Box<dyn Filter>) {
+        self.filters.push(filter);
+    }
+
+    pub fn apply(&mut self, value: f64, now: Instant) -> f64 {
+        self.filters
+            .iter_mut()
+            .fold(value, |value, filter| filter.apply(value, now))
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(|filter| filter.reset());
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    pub raw: u16,
+    pub filtered: f64,
+}
+
+/// Reads an `AnalogInput` and runs each channel through its filter chain.
+#[derive(Debug)]
+pub struct AnalogSampler {
+    pub input: AnalogInput,
+    chains: HashMap<Channel, FilterChain>,
+    latest: Vec<Sample>,
+}
+
+impl AnalogSampler {
+    pub fn new(input: AnalogInput) -> Self {
+        AnalogSampler {
+            input,
+            chains: HashMap::new(),
+            latest: Vec::new(),
+        }
+    }
+
+    pub fn set_filters(
+        &mut self,
+        channel: Channel,
+        specs: &[FilterSpec],
+    ) -> Result<(), FilterError> {
+        self.chains.insert(channel, FilterChain::from_specs(specs)?);
+        Ok(())
+    }
+
+    pub fn add_filter(&mut self, channel: Channel, filter: Box<dyn Filter>) {
+        self.chains.entry(channel).or_default().push(filter);
+    }
+
+    pub fn latest(&self) -> &[Sample] {
+        &self.latest
+    }
+
+    pub async fn sample(&mut self) -> Result<&[Sample], AnalogInputError> {
+        let readings = self.input.read_input_channels().await?;
+        let now = Instant::now();
+        self.latest = readings
+            .iter()
+            .enumerate()
+            .map(|(index, raw)| {
+                let chain = Channel::try_from(index as u8)
+                    .ok()
+                    .and_then(|channel| self.chains.get_mut(&channel));
+                Sample {
+                    raw: *raw,
+                    filtered: match chain {
+                        Some(chain) => chain.apply(*raw as f64, now),
+                        None => *raw as f64,
+                    },
+                }
+            })
+            .collect();
+        Ok(&self.latest)
+    }
+}
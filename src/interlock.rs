@@ -0,0 +1,283 @@
+use crate::{
+    analog_out::{AnalogOutput, AnalogOutputError},
+    common::Channel,
+    digital::{Action, DigitalIO, DigitalIOError},
+    ThreadSafeContext,
+};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelRef {
+    pub unit_id: u8,
+    pub channel: Channel,
+}
+
+impl ChannelRef {
+    pub fn new(unit_id: u8, channel: Channel) -> Self {
+        ChannelRef { unit_id, channel }
+    }
+}
+
+impl fmt::Display for ChannelRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unit {} {:?}", self.unit_id, self.channel)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum InterlockError {
+    #[error("Interlock: {output} cannot turn on while {other} is on")]
+    MutuallyExclusive {
+        output: ChannelRef,
+        other: ChannelRef,
+    },
+    #[error("Interlock: {output} requires input {input} to read `{required}`")]
+    PermissiveNotMet {
+        output: ChannelRef,
+        input: ChannelRef,
+        required: bool,
+    },
+    #[error("Interlock: {output} must stay on for another {remaining:?}")]
+    MinimumOnTime {
+        output: ChannelRef,
+        remaining: Duration,
+    },
+    #[error("Interlock: {output} must stay off for another {remaining:?}")]
+    MinimumOffTime {
+        output: ChannelRef,
+        remaining: Duration,
+    },
+    #[error("Interlock: {output} has reached {max_starts} starts in the last hour")]
+    TooManyStarts { output: ChannelRef, max_starts: u32 },
+    #[error("Digital IO Error: `{0}`")]
+    DigitalIO(#[from] DigitalIOError),
+    #[error("Analog Output Error: `{0}`")]
+    AnalogOutput(#[from] AnalogOutputError),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputKind {
+    Digital,
+    // An analog output counts as on while its value is non-zero
+    Analog,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rule {
+    MutuallyExclusive {
+        first: (ChannelRef, OutputKind),
+        second: (ChannelRef, OutputKind),
+    },
+    // `input` is a discrete input on a DigitalIO module
+    Permissive {
+        output: ChannelRef,
+        input: ChannelRef,
+        required: bool,
+    },
+    MinimumTimes {
+        output: ChannelRef,
+        min_on: Duration,
+        min_off: Duration,
+    },
+    MaxStartsPerHour {
+        output: ChannelRef,
+        max_starts: u32,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+struct OutputHistory {
+    on: Option<bool>,
+    changed: Option<Instant>,
+    starts: VecDeque<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    rules: Vec<Rule>,
+    history: HashMap<ChannelRef, OutputHistory>,
+}
+
+/// Rules checked before an output is written.
+///
+/// Clones share rules and history, so interlocked writes from different tasks
+/// are checked and written one at a time.
+#[derive(Debug, Clone)]
+pub struct Interlocks {
+    context: ThreadSafeContext,
+    state: Arc<Mutex<State>>,
+}
+
+impl Interlocks {
+    pub fn new(context: ThreadSafeContext) -> Self {
+        Interlocks {
+            context,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    pub async fn add_rule(&self, rule: Rule) {
+        self.state.lock().await.rules.push(rule);
+    }
+
+    pub async fn write_output_channel<const N: usize>(
+        &self,
+        io: &mut DigitalIO<N>,
+        channel: Channel,
+        action: Action,
+    ) -> Result<(), InterlockError> {
+        let output = ChannelRef::new(io.unit_id, channel);
+        let on = action == Action::On;
+        let mut state = self.state.lock().await;
+        self.check(&state, output, on).await?;
+        io.write_output_channel(channel, action).await?;
+        record(&mut state, output, on);
+        Ok(())
+    }
+
+    pub async fn write_output_channel_value(
+        &self,
+        output: &mut AnalogOutput,
+        channel: Channel,
+        value: u16,
+    ) -> Result<(), InterlockError> {
+        let target = ChannelRef::new(output.unit_id, channel);
+        let on = value != 0;
+        let mut state = self.state.lock().await;
+        self.check(&state, target, on).await?;
+        output.write_output_channel_value(channel, value).await?;
+        record(&mut state, target, on);
+        Ok(())
+    }
+
+    async fn check(
+        &self,
+        state: &State,
+        output: ChannelRef,
+        on: bool,
+    ) -> Result<(), InterlockError> {
+        let now = Instant::now();
+        let history = state.history.get(&output);
+        let changing = history.and_then(|history| history.on) != Some(on);
+
+        for rule in &state.rules {
+            match *rule {
+                Rule::MutuallyExclusive { first, second } if on => {
+                    let other = if first.0 == output {
+                        second
+                    } else if second.0 == output {
+                        first
+                    } else {
+                        continue;
+                    };
+                    if self.is_on(other.0, other.1).await? {
+                        return Err(InterlockError::MutuallyExclusive {
+                            output,
+                            other: other.0,
+                        });
+                    }
+                }
+                Rule::Permissive {
+                    output: guarded,
+                    input,
+                    required,
+                } if on && guarded == output => {
+                    let value = DigitalIO::<32>::with_channels(input.unit_id, self.context.clone())
+                        .read_input_channel_status(input.channel)
+                        .await?;
+                    if value != required {
+                        return Err(InterlockError::PermissiveNotMet {
+                            output,
+                            input,
+                            required,
+                        });
+                    }
+                }
+                Rule::MinimumTimes {
+                    output: guarded,
+                    min_on,
+                    min_off,
+                } if changing && guarded == output => {
+                    let Some(changed) = history.and_then(|history| history.changed) else {
+                        continue;
+                    };
+                    let elapsed = now.saturating_duration_since(changed);
+                    let minimum = if on { min_off } else { min_on };
+                    if elapsed < minimum {
+                        let remaining = minimum - elapsed;
+                        return Err(if on {
+                            InterlockError::MinimumOffTime { output, remaining }
+                        } else {
+                            InterlockError::MinimumOnTime { output, remaining }
+                        });
+                    }
+                }
+                Rule::MaxStartsPerHour {
+                    output: guarded,
+                    max_starts,
+                } if on && changing && guarded == output => {
+                    let starts = history
+                        .map(|history| {
+                            history
+                                .starts
+                                .iter()
+                                .filter(|start| now.saturating_duration_since(**start) < HOUR)
+                                .count()
+                        })
+                        .unwrap_or_default();
+                    if starts >= max_starts as usize {
+                        return Err(InterlockError::TooManyStarts { output, max_starts });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn is_on(&self, target: ChannelRef, kind: OutputKind) -> Result<bool, InterlockError> {
+        match kind {
+            // Channels are not checked against the board size, the device rejects them
+            OutputKind::Digital => Ok(DigitalIO::<32>::with_channels(
+                target.unit_id,
+                self.context.clone(),
+            )
+            .read_output_channel_status(target.channel)
+            .await?),
+            OutputKind::Analog => {
+                let value = AnalogOutput::new(target.unit_id, self.context.clone())
+                    .read_output_channel_value(target.channel)
+                    .await?;
+                Ok(value != 0)
+            }
+        }
+    }
+}
+
+fn record(state: &mut State, output: ChannelRef, on: bool) {
+    let now = Instant::now();
+    let history = state.history.entry(output).or_default();
+    if history.on == Some(on) {
+        return;
+    }
+    if on {
+        history.starts.push_back(now);
+        while history
+            .starts
+            .front()
+            .is_some_and(|start| now.saturating_duration_since(*start) >= HOUR)
+        {
+            history.starts.pop_front();
+        }
+    }
+    history.on = Some(on);
+    history.changed = Some(now);
+}
@@ -1,50 +1,161 @@
+pub mod alarm;
 pub mod analog_in;
 pub mod analog_out;
+pub mod arbiter;
+pub mod broadcast;
+pub mod calibration;
 pub mod common;
+pub mod counter;
 pub mod digital;
+pub mod edges;
+pub mod filter;
+pub mod interlock;
+pub mod pid;
+pub mod poller;
+pub mod profile;
+pub mod pulse;
+pub mod ramp;
+pub mod registry;
+pub mod relay;
+#[cfg(feature = "serial")]
+pub mod serial;
+pub mod watchdog;
+pub mod waveform;
 
+use arbiter::{Arbiter, BusMetrics, Permit, Priority};
+use common::Baudrates;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio::time::Instant;
 use tokio_modbus::client::{Client, Context, Reader, Writer};
 use tokio_modbus::slave::SlaveContext;
 use tokio_modbus::{Address, Quantity, Request, Response, Result, Slave};
 
+/// Time given to a broadcast on top of its frame, for the serial adapter to
+/// pass it on to the wire
+pub const MIN_SEND_WINDOW: Duration = Duration::from_millis(20);
+
+#[derive(Debug)]
+struct Bus {
+    context: Context,
+    unit: Option<u8>,
+    // When the line has been silent long enough for the next request
+    idle_at: Option<Instant>,
+    silent_interval: Duration,
+    turnaround_delays: HashMap<u8, Duration>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ThreadSafeContext {
-    inner: Arc<Mutex<Context>>,
+    inner: Arc<Mutex<Bus>>,
+    arbiter: Arc<Arbiter>,
+    priority: Priority,
+    timeout: Option<Duration>,
 }
 
 impl ThreadSafeContext {
+    /// Paced for 9600 baud, the modules' factory setting, until told otherwise.
     pub fn new(context: Context) -> Self {
+        Self::new_with_baudrate(context, Baudrates::B9600)
+    }
+
+    pub fn new_with_baudrate(context: Context, baudrate: Baudrates) -> Self {
         Self {
-            inner: Arc::new(Mutex::new(context)),
+            inner: Arc::new(Mutex::new(Bus {
+                context,
+                unit: None,
+                idle_at: None,
+                silent_interval: baudrate.silent_interval(),
+                turnaround_delays: HashMap::new(),
+            })),
+            arbiter: Arc::new(Arbiter::new()),
+            priority: Priority::default(),
+            timeout: None,
         }
     }
 
+    /// Fails requests that get no response within `timeout`, RTU has no timeout of its own.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Priority of requests made through this handle, clones keep it.
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// How long a request can be passed over by higher priorities before it
+    /// goes next regardless. Shared by every handle on the bus.
+    pub fn with_starvation_limit(self, limit: Duration) -> Self {
+        self.arbiter.set_starvation_limit(limit);
+        self
+    }
+
+    pub fn metrics(&self) -> BusMetrics {
+        self.arbiter.metrics()
+    }
+
     pub fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            arbiter: Arc::clone(&self.arbiter),
+            priority: self.priority,
+            timeout: self.timeout,
         }
     }
 
+    /// Holds the bus so a sequence of requests can't be interleaved with other users.
+    pub async fn lock(&self) -> BusGuard<'_> {
+        let permit = self.arbiter.acquire(self.priority).await;
+        BusGuard {
+            bus: self.inner.lock().await,
+            _permit: permit,
+            owner: self,
+        }
+    }
+
+    /// Holds the bus with `slave` selected, so nothing else can redirect the
+    /// requests made through the guard.
+    pub async fn lock_slave(&self, slave: Slave) -> BusGuard<'_> {
+        let mut bus = self.lock().await;
+        bus.set_slave(slave);
+        bus
+    }
+
     pub async fn set_slave(&self, slave: Slave) {
-        let mut ctx = self.inner.lock().await;
-        ctx.set_slave(slave);
+        self.lock().await.set_slave(slave);
     }
 
-    pub async fn call(&mut self, request: Request<'_>) -> Result<Response> {
-        let mut ctx = self.inner.lock().await;
-        ctx.call(request).await
+    /// Paces the bus for `baudrate`, e.g. once the port has been reopened after
+    /// `set_uart_parameters`. Pacing is shared by every handle on the bus.
+    pub async fn set_baudrate(&self, baudrate: Baudrates) {
+        self.lock().await.set_baudrate(baudrate);
+    }
+
+    pub async fn set_silent_interval(&self, silent_interval: Duration) {
+        self.lock().await.set_silent_interval(silent_interval);
+    }
+
+    pub async fn set_turnaround_delay(&self, unit_id: u8, delay: Duration) {
+        self.lock().await.set_turnaround_delay(unit_id, delay);
     }
 
     pub async fn disconnect(&mut self) -> std::io::Result<()> {
-        let mut ctx = self.inner.lock().await;
-        ctx.disconnect().await
+        self.lock().await.disconnect().await
+    }
+
+    pub async fn call(&mut self, request: Request<'_>) -> Result<Response> {
+        self.lock().await.call(request).await
     }
 
     pub async fn read_coils(&mut self, addr: Address, cnt: Quantity) -> Result<Vec<bool>> {
-        let mut ctx = self.inner.lock().await;
-        ctx.read_coils(addr, cnt).await
+        self.lock().await.read_coils(addr, cnt).await
     }
 
     pub async fn read_discrete_inputs(
@@ -52,8 +163,7 @@ impl ThreadSafeContext {
         addr: Address,
         cnt: Quantity,
     ) -> Result<Vec<bool>> {
-        let mut ctx = self.inner.lock().await;
-        ctx.read_discrete_inputs(addr, cnt).await
+        self.lock().await.read_discrete_inputs(addr, cnt).await
     }
 
     pub async fn read_holding_registers(
@@ -61,13 +171,11 @@ impl ThreadSafeContext {
         addr: Address,
         cnt: Quantity,
     ) -> Result<Vec<u16>> {
-        let mut ctx = self.inner.lock().await;
-        ctx.read_holding_registers(addr, cnt).await
+        self.lock().await.read_holding_registers(addr, cnt).await
     }
 
     pub async fn read_input_registers(&mut self, addr: Address, cnt: Quantity) -> Result<Vec<u16>> {
-        let mut ctx = self.inner.lock().await;
-        ctx.read_input_registers(addr, cnt).await
+        self.lock().await.read_input_registers(addr, cnt).await
     }
 
     pub async fn read_write_multiple_registers(
@@ -77,29 +185,29 @@ impl ThreadSafeContext {
         write_addr: Address,
         write_data: &[u16],
     ) -> Result<Vec<u16>> {
-        let mut ctx = self.inner.lock().await;
-        ctx.read_write_multiple_registers(read_addr, read_count, write_addr, write_data)
+        self.lock()
+            .await
+            .read_write_multiple_registers(read_addr, read_count, write_addr, write_data)
             .await
     }
 
     pub async fn write_single_coil(&mut self, addr: Address, coil: bool) -> Result<()> {
-        let mut ctx = self.inner.lock().await;
-        ctx.write_single_coil(addr, coil).await
+        self.lock().await.write_single_coil(addr, coil).await
     }
 
     pub async fn write_single_register(&mut self, addr: Address, word: u16) -> Result<()> {
-        let mut ctx = self.inner.lock().await;
-        ctx.write_single_register(addr, word).await
+        self.lock().await.write_single_register(addr, word).await
     }
 
     pub async fn write_multiple_coils(&mut self, addr: Address, coils: &[bool]) -> Result<()> {
-        let mut ctx = self.inner.lock().await;
-        ctx.write_multiple_coils(addr, coils).await
+        self.lock().await.write_multiple_coils(addr, coils).await
     }
 
     pub async fn write_multiple_registers(&mut self, addr: Address, words: &[u16]) -> Result<()> {
-        let mut ctx = self.inner.lock().await;
-        ctx.write_multiple_registers(addr, words).await
+        self.lock()
+            .await
+            .write_multiple_registers(addr, words)
+            .await
     }
 
     pub async fn masked_write_register(
@@ -108,7 +216,189 @@ impl ThreadSafeContext {
         and_mask: u16,
         or_mask: u16,
     ) -> Result<()> {
-        let mut ctx = self.inner.lock().await;
-        ctx.masked_write_register(addr, and_mask, or_mask).await
+        self.lock()
+            .await
+            .masked_write_register(addr, and_mask, or_mask)
+            .await
+    }
+}
+
+/// Exclusive access to the bus, requests made through it are paced and timed
+/// like those on `ThreadSafeContext`.
+#[derive(Debug)]
+pub struct BusGuard<'a> {
+    bus: MutexGuard<'a, Bus>,
+    // Dropped after the bus so the next holder finds it unlocked
+    _permit: Permit,
+    owner: &'a ThreadSafeContext,
+}
+
+impl BusGuard<'_> {
+    pub fn set_slave(&mut self, slave: Slave) {
+        self.bus.unit = Some(slave.0);
+        self.bus.context.set_slave(slave);
+    }
+
+    pub fn set_baudrate(&mut self, baudrate: Baudrates) {
+        self.set_silent_interval(baudrate.silent_interval());
+    }
+
+    /// Minimum time the line is left idle between the end of one transaction and the next request.
+    pub fn set_silent_interval(&mut self, silent_interval: Duration) {
+        self.bus.silent_interval = silent_interval;
+    }
+
+    /// Extra idle time after talking to `unit_id`, for modules that are slow to release the bus.
+    pub fn set_turnaround_delay(&mut self, unit_id: u8, delay: Duration) {
+        self.bus.turnaround_delays.insert(unit_id, delay);
+    }
+
+    pub async fn disconnect(&mut self) -> std::io::Result<()> {
+        self.bus.context.disconnect().await
+    }
+
+    async fn paced<'c, T, F>(&'c mut self, request: impl FnOnce(&'c mut Context) -> F) -> Result<T>
+    where
+        F: Future<Output = Result<T>> + 'c,
+    {
+        if let Some(idle_at) = self.bus.idle_at {
+            tokio::time::sleep_until(idle_at).await;
+        }
+        let owner = self.owner;
+        let bus = &mut *self.bus;
+        let request = request(&mut bus.context);
+        let result = match owner.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, request)
+                .await
+                .unwrap_or_else(|_| Err(io::Error::from(io::ErrorKind::TimedOut).into())),
+            None => request.await,
+        };
+        let turnaround = bus
+            .unit
+            .and_then(|unit| bus.turnaround_delays.get(&unit))
+            .copied()
+            .unwrap_or_default();
+        bus.idle_at = Some(Instant::now() + bus.silent_interval + turnaround);
+        result
+    }
+
+    pub async fn call(&mut self, request: Request<'_>) -> Result<Response> {
+        self.paced(|context| context.call(request)).await
+    }
+
+    /// Sends `request` to every unit through unit 0, then keeps the bus for
+    /// `turnaround` so the units can act on it before the next request.
+    ///
+    /// No unit answers a broadcast, so the frame is given its time on the wire
+    /// to be written and isn't waited on after that. That time is worked out
+    /// at the slowest baudrate the modules support with [`MIN_SEND_WINDOW`]
+    /// on top, whatever pacing the bus is set to. The transport writes and
+    /// waits for a reply in one call, so a write that stalls for longer than
+    /// that is cut off and still reported as sent. Only errors from the
+    /// transport in that time are returned. The previously selected slave is
+    /// selected again afterwards.
+    pub async fn broadcast(&mut self, request: Request<'_>, turnaround: Duration) -> Result<()> {
+        if let Some(idle_at) = self.bus.idle_at {
+            tokio::time::sleep_until(idle_at).await;
+        }
+        let previous = self.bus.unit;
+        self.bus.context.set_slave(Slave::broadcast());
+        // The silent interval is 3.5 character times
+        let send_window =
+            MIN_SEND_WINDOW + Baudrates::B4800.silent_interval() * 2 * frame_len(&request) / 7;
+        let result = tokio::time::timeout(send_window, self.bus.context.call(request)).await;
+        if let Some(unit) = previous {
+            self.bus.context.set_slave(Slave(unit));
+        }
+        let result = match result {
+            // A unit answered anyway, the request still went out
+            Ok(Ok(Ok(_))) | Err(_) => Ok(Ok(())),
+            Ok(Ok(Err(exception))) => Ok(Err(exception)),
+            Ok(Err(err)) => Err(err),
+        };
+        tokio::time::sleep(turnaround).await;
+        self.bus.idle_at = Some(Instant::now() + self.bus.silent_interval);
+        result
+    }
+
+    pub async fn read_coils(&mut self, addr: Address, cnt: Quantity) -> Result<Vec<bool>> {
+        self.paced(|context| context.read_coils(addr, cnt)).await
+    }
+
+    pub async fn read_discrete_inputs(
+        &mut self,
+        addr: Address,
+        cnt: Quantity,
+    ) -> Result<Vec<bool>> {
+        self.paced(|context| context.read_discrete_inputs(addr, cnt))
+            .await
+    }
+
+    pub async fn read_holding_registers(
+        &mut self,
+        addr: Address,
+        cnt: Quantity,
+    ) -> Result<Vec<u16>> {
+        self.paced(|context| context.read_holding_registers(addr, cnt))
+            .await
+    }
+
+    pub async fn read_input_registers(&mut self, addr: Address, cnt: Quantity) -> Result<Vec<u16>> {
+        self.paced(|context| context.read_input_registers(addr, cnt))
+            .await
+    }
+
+    pub async fn read_write_multiple_registers(
+        &mut self,
+        read_addr: Address,
+        read_count: Quantity,
+        write_addr: Address,
+        write_data: &[u16],
+    ) -> Result<Vec<u16>> {
+        self.paced(|context| {
+            context.read_write_multiple_registers(read_addr, read_count, write_addr, write_data)
+        })
+        .await
+    }
+
+    pub async fn write_single_coil(&mut self, addr: Address, coil: bool) -> Result<()> {
+        self.paced(|context| context.write_single_coil(addr, coil))
+            .await
+    }
+
+    pub async fn write_single_register(&mut self, addr: Address, word: u16) -> Result<()> {
+        self.paced(|context| context.write_single_register(addr, word))
+            .await
+    }
+
+    pub async fn write_multiple_coils(&mut self, addr: Address, coils: &[bool]) -> Result<()> {
+        self.paced(|context| context.write_multiple_coils(addr, coils))
+            .await
+    }
+
+    pub async fn write_multiple_registers(&mut self, addr: Address, words: &[u16]) -> Result<()> {
+        self.paced(|context| context.write_multiple_registers(addr, words))
+            .await
+    }
+
+    pub async fn masked_write_register(
+        &mut self,
+        addr: Address,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<()> {
+        self.paced(|context| context.masked_write_register(addr, and_mask, or_mask))
+            .await
+    }
+}
+
+// Bytes in the RTU frame for `request`, including the address and CRC
+fn frame_len(request: &Request<'_>) -> u32 {
+    match request {
+        Request::WriteSingleCoil(..) | Request::WriteSingleRegister(..) => 8,
+        Request::WriteMultipleCoils(_, coils) => 9 + coils.len().div_ceil(8) as u32,
+        Request::WriteMultipleRegisters(_, words) => 9 + 2 * words.len() as u32,
+        // The longest frame RTU allows
+        _ => 256,
     }
 }
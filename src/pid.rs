@@ -0,0 +1,276 @@
+use crate::{
+    analog_out::{AnalogOutput, AnalogOutputError},
+    calibration::Calibration,
+    common::Channel,
+    poller::{Quality, ScanValues, Snapshot},
+};
+use thiserror::Error;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+#[derive(Error, Debug)]
+pub enum PidError {
+    #[error("Process value is unavailable: `{0}`")]
+    ProcessValueUnavailable(String),
+    #[error("Output value `{0}` cannot be converted to a raw value")]
+    InvalidOutput(f64),
+    #[error("Output limits `{0}..={1}` are not a range")]
+    InvalidLimits(f64, f64),
+    #[error("Analog Output Error: `{0}`")]
+    AnalogOutput(#[from] AnalogOutputError),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tunings {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Automatic,
+    Manual,
+}
+
+/// Positional PID with integrator clamping and derivative on measurement.
+#[derive(Debug, Clone)]
+pub struct Pid {
+    pub tunings: Tunings,
+    pub setpoint: f64,
+    pub output_min: f64,
+    pub output_max: f64,
+    mode: Mode,
+    integral: f64,
+    output: f64,
+    last_process_value: Option<f64>,
+}
+
+impl Pid {
+    /// Fails unless the limits are finite with `output_min <= output_max`.
+    pub fn new(tunings: Tunings, output_min: f64, output_max: f64) -> Result<Self, PidError> {
+        if !(output_min.is_finite() && output_max.is_finite() && output_min <= output_max) {
+            return Err(PidError::InvalidLimits(output_min, output_max));
+        }
+        Ok(Pid {
+            tunings,
+            setpoint: 0.0,
+            output_min,
+            output_max,
+            mode: Mode::Automatic,
+            integral: 0.0,
+            output: output_min,
+            last_process_value: None,
+        })
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn output(&self) -> f64 {
+        self.output
+    }
+
+    // Switching to automatic starts from the current output so there is no bump
+    pub fn set_mode(&mut self, mode: Mode) {
+        if self.mode == Mode::Manual && mode == Mode::Automatic {
+            self.integral = self.output;
+            self.last_process_value = None;
+        }
+        self.mode = mode;
+    }
+
+    /// Starts from `output`, typically the actuator's current value, so the
+    /// first control action doesn't bump it.
+    pub fn initialize(&mut self, output: f64) {
+        self.output = output.clamp(self.output_min, self.output_max);
+        self.integral = self.output;
+        self.last_process_value = None;
+    }
+
+    pub fn set_manual_output(&mut self, output: f64) {
+        self.output = output.clamp(self.output_min, self.output_max);
+    }
+
+    pub fn set_tunings(&mut self, tunings: Tunings) {
+        self.tunings = tunings;
+    }
+
+    pub fn update(&mut self, process_value: f64, dt: f64) -> f64 {
+        if self.mode == Mode::Manual || dt <= 0.0 {
+            self.last_process_value = Some(process_value);
+            return self.output;
+        }
+        let error = self.setpoint - process_value;
+        // Clamping the integral term stops it winding up while the output is saturated
+        self.integral =
+            (self.integral + self.tunings.ki * error * dt).clamp(self.output_min, self.output_max);
+        let derivative = match self.last_process_value {
+            Some(last) => -(process_value - last) / dt,
+            None => 0.0,
+        };
+        self.last_process_value = Some(process_value);
+        self.output = (self.tunings.kp * error + self.integral + self.tunings.kd * derivative)
+            .clamp(self.output_min, self.output_max);
+        self.output
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LoopCommand {
+    Setpoint(f64),
+    Tunings(Tunings),
+    Mode(Mode),
+    ManualOutput(f64),
+}
+
+/// Links an analog input scan from the `BusPoller` to an analog output channel.
+///
+/// Each new snapshot is one sample, so the sample time is the scan period.
+/// `input_scaling` maps raw readings to engineering units and `output_scaling`
+/// maps raw output values to engineering units, it is inverted for writes.
+#[derive(Debug)]
+pub struct PidLoop {
+    pub pid: Pid,
+    pub input_channel: Channel,
+    pub input_scaling: Calibration,
+    pub output: AnalogOutput,
+    pub output_channel: Channel,
+    pub output_scaling: Calibration,
+}
+
+#[derive(Debug, Clone)]
+pub struct PidLoopHandle {
+    commands: mpsc::UnboundedSender<LoopCommand>,
+    status: watch::Receiver<LoopStatus>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoopStatus {
+    pub process_value: Option<f64>,
+    pub output: f64,
+    pub last_error: Option<String>,
+}
+
+impl PidLoopHandle {
+    pub fn send(&self, command: LoopCommand) {
+        let _ = self.commands.send(command);
+    }
+
+    pub fn status(&self) -> LoopStatus {
+        self.status.borrow().clone()
+    }
+}
+
+impl PidLoop {
+    pub fn spawn(self, process: watch::Receiver<Snapshot>) -> (PidLoopHandle, JoinHandle<()>) {
+        let (commands, command_receiver) = mpsc::unbounded_channel();
+        let (status_sender, status) = watch::channel(LoopStatus::default());
+        let task = tokio::spawn(self.run(process, command_receiver, status_sender));
+        (PidLoopHandle { commands, status }, task)
+    }
+
+    fn process_value(&self, snapshot: &Snapshot) -> Result<f64, PidError> {
+        if snapshot.quality != Quality::Good {
+            return Err(PidError::ProcessValueUnavailable(
+                snapshot
+                    .last_error
+                    .clone()
+                    .unwrap_or_else(|| "no good scan".to_string()),
+            ));
+        }
+        match &snapshot.values {
+            ScanValues::Analog(values) => values
+                .get(self.input_channel as usize)
+                .map(|raw| self.input_scaling.apply(*raw as f64))
+                .ok_or_else(|| PidError::ProcessValueUnavailable("missing channel".to_string())),
+            _ => Err(PidError::ProcessValueUnavailable(
+                "scan is not analog".to_string(),
+            )),
+        }
+    }
+
+    pub async fn step(&mut self, process_value: f64, dt: f64) -> Result<f64, PidError> {
+        let output = self.pid.update(process_value, dt);
+        let raw = self
+            .output_scaling
+            .invert(output)
+            .map_err(|_| PidError::InvalidOutput(output))?
+            .round();
+        if !(0.0..=u16::MAX as f64).contains(&raw) {
+            return Err(PidError::InvalidOutput(output));
+        }
+        self.output
+            .write_output_channel_value(self.output_channel, raw as u16)
+            .await?;
+        Ok(output)
+    }
+
+    // Runs until the snapshot sender or every handle is dropped
+    async fn run(
+        mut self,
+        mut process: watch::Receiver<Snapshot>,
+        mut commands: mpsc::UnboundedReceiver<LoopCommand>,
+        status: watch::Sender<LoopStatus>,
+    ) {
+        let mut last_sample: Option<Instant> = None;
+        match self
+            .output
+            .read_output_channel_value(self.output_channel)
+            .await
+        {
+            Ok(raw) => self.pid.initialize(self.output_scaling.apply(raw as f64)),
+            Err(err) => log::warn!("pid loop could not read the actuator: {}", err),
+        }
+        loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(LoopCommand::Setpoint(setpoint)) => self.pid.setpoint = setpoint,
+                    Some(LoopCommand::Tunings(tunings)) => self.pid.set_tunings(tunings),
+                    Some(LoopCommand::Mode(mode)) => self.pid.set_mode(mode),
+                    Some(LoopCommand::ManualOutput(output)) => self.pid.set_manual_output(output),
+                    None => return,
+                },
+                changed = process.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    let snapshot = process.borrow_and_update().clone();
+                    // Only a new scan is a new sample
+                    if snapshot.quality == Quality::Good && snapshot.timestamp == last_sample {
+                        continue;
+                    }
+                    let dt = match (last_sample, snapshot.timestamp) {
+                        (Some(last), Some(now)) => now.saturating_duration_since(last).as_secs_f64(),
+                        _ => 0.0,
+                    };
+                    if snapshot.timestamp.is_some() {
+                        last_sample = snapshot.timestamp;
+                    }
+                    let result = match self.process_value(&snapshot) {
+                        // There is no control action without a sample time, the
+                        // first sample only primes the controller
+                        Ok(process_value) if dt <= 0.0 => {
+                            self.pid.update(process_value, dt);
+                            Ok(process_value)
+                        }
+                        Ok(process_value) => self.step(process_value, dt).await.map(|_| process_value),
+                        Err(err) => Err(err),
+                    };
+                    status.send_modify(|status| {
+                        status.output = self.pid.output();
+                        match result {
+                            Ok(process_value) => {
+                                status.process_value = Some(process_value);
+                                status.last_error = None;
+                            }
+                            Err(err) => status.last_error = Some(err.to_string()),
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
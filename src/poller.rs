@@ -0,0 +1,202 @@
+use crate::{
+    analog_in::AnalogInput,
+    analog_out::AnalogOutput,
+    digital::{AnyDigitalIO, Width},
+    ThreadSafeContext,
+};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+#[derive(Error, Debug)]
+pub enum PollerError {
+    #[error("Scan period must be greater than zero")]
+    InvalidPeriod,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ScanKind {
+    DigitalInputs(Width),
+    DigitalOutputs(Width),
+    AnalogInputs,
+    AnalogOutputs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanValues {
+    Empty,
+    Digital(Vec<bool>),
+    Analog(Vec<u16>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Quality {
+    // No scan has completed yet
+    Uncertain,
+    Good,
+    // The last scan failed, values are from the last good scan
+    Bad,
+}
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub values: ScanValues,
+    // Time of the last successful scan
+    pub timestamp: Option<Instant>,
+    pub quality: Quality,
+    pub last_error: Option<String>,
+    pub overruns: u64,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Snapshot {
+            values: ScanValues::Empty,
+            timestamp: None,
+            quality: Quality::Uncertain,
+            last_error: None,
+            overruns: 0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Overrun {
+    pub unit_id: u8,
+    pub kind: ScanKind,
+    pub missed_scans: u32,
+    pub late_by: Duration,
+}
+
+#[derive(Debug)]
+struct Scan {
+    unit_id: u8,
+    kind: ScanKind,
+    period: Duration,
+    next_due: Instant,
+    sender: watch::Sender<Snapshot>,
+}
+
+/// Owns the bus and scans each configured device at its own rate.
+#[derive(Debug)]
+pub struct BusPoller {
+    context: ThreadSafeContext,
+    scans: Vec<Scan>,
+    overruns: broadcast::Sender<Overrun>,
+}
+
+impl BusPoller {
+    pub fn new(context: ThreadSafeContext) -> Self {
+        let (overruns, _) = broadcast::channel(16);
+        BusPoller {
+            context,
+            scans: Vec::new(),
+            overruns,
+        }
+    }
+
+    pub fn context(&self) -> ThreadSafeContext {
+        self.context.clone()
+    }
+
+    pub fn add_scan(
+        &mut self,
+        unit_id: u8,
+        kind: ScanKind,
+        period: Duration,
+    ) -> Result<watch::Receiver<Snapshot>, PollerError> {
+        if period.is_zero() {
+            return Err(PollerError::InvalidPeriod);
+        }
+        let (sender, receiver) = watch::channel(Snapshot::default());
+        self.scans.push(Scan {
+            unit_id,
+            kind,
+            period,
+            next_due: Instant::now(),
+            sender,
+        });
+        Ok(receiver)
+    }
+
+    pub fn subscribe_overruns(&self) -> broadcast::Receiver<Overrun> {
+        self.overruns.subscribe()
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    /// Runs until every snapshot receiver has been dropped.
+    pub async fn run(mut self) {
+        loop {
+            self.scans.retain(|scan| !scan.sender.is_closed());
+            let Some(index) = (0..self.scans.len()).min_by_key(|i| self.scans[*i].next_due) else {
+                return;
+            };
+            let due = self.scans[index].next_due;
+            tokio::time::sleep_until(due).await;
+
+            let result = self.read(index).await;
+            let scan = &mut self.scans[index];
+            scan.sender.send_modify(|snapshot| match result {
+                Ok(values) => {
+                    snapshot.values = values;
+                    snapshot.timestamp = Some(Instant::now());
+                    snapshot.quality = Quality::Good;
+                    snapshot.last_error = None;
+                }
+                Err(err) => {
+                    snapshot.quality = Quality::Bad;
+                    snapshot.last_error = Some(err);
+                }
+            });
+
+            let now = Instant::now();
+            let mut missed_scans = 0;
+            scan.next_due += scan.period;
+            while scan.next_due <= now {
+                scan.next_due += scan.period;
+                missed_scans += 1;
+            }
+            if missed_scans > 0 {
+                scan.sender.send_modify(|snapshot| snapshot.overruns += 1);
+                let _ = self.overruns.send(Overrun {
+                    unit_id: scan.unit_id,
+                    kind: scan.kind,
+                    missed_scans,
+                    late_by: now - due,
+                });
+            }
+        }
+    }
+
+    async fn read(&self, index: usize) -> Result<ScanValues, String> {
+        let scan = &self.scans[index];
+        let context = self.context.clone();
+        match scan.kind {
+            ScanKind::DigitalInputs(width) => AnyDigitalIO::new(scan.unit_id, context, width)
+                .read_input_channels()
+                .await
+                .map(ScanValues::Digital)
+                .map_err(|err| err.to_string()),
+            ScanKind::DigitalOutputs(width) => AnyDigitalIO::new(scan.unit_id, context, width)
+                .read_output_channels()
+                .await
+                .map(ScanValues::Digital)
+                .map_err(|err| err.to_string()),
+            ScanKind::AnalogInputs => AnalogInput::new(scan.unit_id, context)
+                .read_input_channels()
+                .await
+                .map(ScanValues::Analog)
+                .map_err(|err| err.to_string()),
+            ScanKind::AnalogOutputs => AnalogOutput::new(scan.unit_id, context)
+                .read_output_channels()
+                .await
+                .map(ScanValues::Analog)
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,691 @@
+use crate::{
+    common::{
+        Baudrates, CommonHoldingRegisters, DeviceSettings, Parity, SettingsError, WaveshareModbus,
+    },
+    ThreadSafeContext,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+use tokio_modbus::Slave;
+
+#[derive(Error, Debug)]
+pub enum ProfileError {
+    #[error("Modbus Exception Error: `{0}`")]
+    ModbusException(tokio_modbus::ExceptionCode),
+    #[error("Modbus Error: `{0}`")]
+    ModbusError(tokio_modbus::Error),
+    #[error("Unknown register block `{0}`")]
+    UnknownBlock(String),
+    #[error("Index {index} is outside register block `{block}`")]
+    OutOfRange { block: String, index: u16 },
+    #[error("Register block `{0}` is read only")]
+    ReadOnly(String),
+    #[error("Invalid value `{value:?}` for register block `{block}`")]
+    InvalidValue { block: String, value: Value },
+    #[error("Parse Error on line {0}: `{1}`")]
+    Parse(usize, String),
+    #[error("IO Error: `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("Settings Error: `{0}`")]
+    Settings(#[from] SettingsError),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RegisterKind {
+    Coil,
+    DiscreteInput,
+    HoldingRegister,
+    InputRegister,
+}
+
+impl RegisterKind {
+    pub fn is_writable(&self) -> bool {
+        matches!(self, RegisterKind::Coil | RegisterKind::HoldingRegister)
+    }
+
+    fn is_bit(&self) -> bool {
+        matches!(self, RegisterKind::Coil | RegisterKind::DiscreteInput)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Count {
+    // One register per channel of the device
+    Channels,
+    Fixed(u16),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueType {
+    Bool,
+    U16,
+    // Only the listed raw values may be written
+    Enum(Vec<(u16, String)>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    U16(u16),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterBlock {
+    pub name: String,
+    pub kind: RegisterKind,
+    pub address: u16,
+    pub count: Count,
+    pub value_type: ValueType,
+}
+
+impl RegisterBlock {
+    pub fn new(
+        name: &str,
+        kind: RegisterKind,
+        address: u16,
+        count: Count,
+        value_type: ValueType,
+    ) -> Self {
+        RegisterBlock {
+            name: name.to_string(),
+            kind,
+            address,
+            count,
+            value_type,
+        }
+    }
+
+    /// Name of an enum value, if the block is an enum.
+    pub fn value_name(&self, raw: u16) -> Option<&str> {
+        match &self.value_type {
+            ValueType::Enum(values) => values
+                .iter()
+                .find(|(value, _)| *value == raw)
+                .map(|(_, name)| name.as_str()),
+            _ => None,
+        }
+    }
+
+    fn is_valid(&self, value: &Value) -> bool {
+        match (&self.value_type, value) {
+            (ValueType::Bool, Value::Bool(_)) => true,
+            (ValueType::U16, Value::U16(_)) => true,
+            (ValueType::Enum(values), Value::U16(raw)) => {
+                values.iter().any(|(value, _)| value == raw)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Register map of a Waveshare-compatible module.
+///
+/// Profiles can be built in code or loaded from a text file, see `FromStr`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceProfile {
+    pub name: String,
+    pub channels: u16,
+    pub blocks: Vec<RegisterBlock>,
+}
+
+impl DeviceProfile {
+    pub fn new(name: &str, channels: u16) -> Self {
+        DeviceProfile {
+            name: name.to_string(),
+            channels,
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn block(mut self, block: RegisterBlock) -> Self {
+        self.blocks.push(block);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegisterBlock> {
+        self.blocks.iter().find(|block| block.name == name)
+    }
+
+    pub fn count(&self, block: &RegisterBlock) -> u16 {
+        match block.count {
+            Count::Channels => self.channels,
+            Count::Fixed(count) => count,
+        }
+    }
+
+    pub fn digital_io() -> Self {
+        DeviceProfile::new("Waveshare Modbus RTU IO 8CH", 8)
+            .block(RegisterBlock::new(
+                "outputs",
+                RegisterKind::Coil,
+                0x0000,
+                Count::Channels,
+                ValueType::Bool,
+            ))
+            .block(RegisterBlock::new(
+                "inputs",
+                RegisterKind::DiscreteInput,
+                0x0000,
+                Count::Channels,
+                ValueType::Bool,
+            ))
+            .block(RegisterBlock::new(
+                "flash_on",
+                RegisterKind::HoldingRegister,
+                0x0200,
+                Count::Channels,
+                ValueType::U16,
+            ))
+            .block(RegisterBlock::new(
+                "flash_off",
+                RegisterKind::HoldingRegister,
+                0x0400,
+                Count::Channels,
+                ValueType::U16,
+            ))
+            .block(RegisterBlock::new(
+                "control_mode",
+                RegisterKind::HoldingRegister,
+                0x1000,
+                Count::Channels,
+                ValueType::Enum(vec![
+                    (0, "command".to_string()),
+                    (1, "linked".to_string()),
+                    (2, "flip".to_string()),
+                ]),
+            ))
+    }
+
+    pub fn analog_input() -> Self {
+        DeviceProfile::new("Waveshare Modbus RTU Analog Input 8CH", 8)
+            .block(RegisterBlock::new(
+                "inputs",
+                RegisterKind::InputRegister,
+                0x0000,
+                Count::Channels,
+                ValueType::U16,
+            ))
+            .block(RegisterBlock::new(
+                "mode",
+                RegisterKind::HoldingRegister,
+                0x1000,
+                Count::Channels,
+                analog_modes(),
+            ))
+    }
+
+    pub fn analog_output() -> Self {
+        DeviceProfile::new("Waveshare Modbus RTU Analog Output 8CH", 8)
+            .block(RegisterBlock::new(
+                "outputs",
+                RegisterKind::HoldingRegister,
+                0x0000,
+                Count::Channels,
+                ValueType::U16,
+            ))
+            .block(RegisterBlock::new(
+                "mode",
+                RegisterKind::HoldingRegister,
+                0x1000,
+                Count::Channels,
+                analog_modes(),
+            ))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProfileError> {
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProfileError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+}
+
+fn analog_modes() -> ValueType {
+    ValueType::Enum(vec![
+        (0, "0-10V".to_string()),
+        (1, "2-10V".to_string()),
+        (2, "0-20mA".to_string()),
+        (3, "4-20mA".to_string()),
+        (4, "raw".to_string()),
+    ])
+}
+
+impl fmt::Display for DeviceProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "name {}", self.name)?;
+        writeln!(f, "channels {}", self.channels)?;
+        for block in &self.blocks {
+            let kind = match block.kind {
+                RegisterKind::Coil => "coil",
+                RegisterKind::DiscreteInput => "discrete_input",
+                RegisterKind::HoldingRegister => "holding_register",
+                RegisterKind::InputRegister => "input_register",
+            };
+            write!(f, "block {} {} {:#06x} ", block.name, kind, block.address)?;
+            match block.count {
+                Count::Channels => write!(f, "channels ")?,
+                Count::Fixed(count) => write!(f, "{} ", count)?,
+            }
+            match &block.value_type {
+                ValueType::Bool => writeln!(f, "bool")?,
+                ValueType::U16 => writeln!(f, "u16")?,
+                ValueType::Enum(values) => {
+                    write!(f, "enum")?;
+                    for (value, name) in values {
+                        write!(f, " {}={}", value, name)?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the line format written by `Display`:
+///
+/// ```text
+/// name Waveshare Modbus RTU Analog Input 8CH
+/// channels 8
+/// block inputs input_register 0x0000 channels u16
+/// block mode holding_register 0x1000 channels enum 0=0-10V 1=2-10V 2=0-20mA 3=4-20mA 4=raw
+/// ```
+impl FromStr for DeviceProfile {
+    type Err = ProfileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut profile = DeviceProfile::new("", 0);
+        for (index, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| ProfileError::Parse(index + 1, reason.to_string());
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "name" => profile.name = rest.trim().to_string(),
+                "channels" => {
+                    profile.channels = rest
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid("invalid channel count"))?
+                }
+                "block" => {
+                    let mut fields = rest.split_whitespace();
+                    let mut next = |what: &str| {
+                        fields
+                            .next()
+                            .ok_or_else(|| invalid(&format!("missing {}", what)))
+                    };
+                    let name = next("name")?.to_string();
+                    let kind = match next("register kind")? {
+                        "coil" => RegisterKind::Coil,
+                        "discrete_input" => RegisterKind::DiscreteInput,
+                        "holding_register" => RegisterKind::HoldingRegister,
+                        "input_register" => RegisterKind::InputRegister,
+                        _ => return Err(invalid("unknown register kind")),
+                    };
+                    let address =
+                        parse_u16(next("address")?).ok_or_else(|| invalid("invalid address"))?;
+                    let count = match next("count")? {
+                        "channels" => Count::Channels,
+                        count => {
+                            Count::Fixed(parse_u16(count).ok_or_else(|| invalid("invalid count"))?)
+                        }
+                    };
+                    if let Count::Fixed(count) = count {
+                        if address as u32 + count as u32 > u16::MAX as u32 + 1 {
+                            return Err(invalid("block runs past the last register address"));
+                        }
+                    }
+                    let value_type = match next("value type")? {
+                        "bool" => ValueType::Bool,
+                        "u16" => ValueType::U16,
+                        "enum" => ValueType::Enum(
+                            fields
+                                .map(|value| {
+                                    let (raw, name) = value.split_once('=')?;
+                                    Some((parse_u16(raw)?, name.to_string()))
+                                })
+                                .collect::<Option<_>>()
+                                .ok_or_else(|| invalid("expected `value=name`"))?,
+                        ),
+                        _ => return Err(invalid("unknown value type")),
+                    };
+                    if kind.is_bit() != (value_type == ValueType::Bool) {
+                        return Err(invalid("bit registers hold bools, word registers do not"));
+                    }
+                    profile.blocks.push(RegisterBlock {
+                        name,
+                        kind,
+                        address,
+                        count,
+                        value_type,
+                    });
+                }
+                _ => return Err(invalid("unknown key")),
+            }
+        }
+        Ok(profile)
+    }
+}
+
+fn parse_u16(value: &str) -> Option<u16> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileSnapshot {
+    pub settings: DeviceSettings,
+    // Raw values of every enum holding register block, by block name
+    pub blocks: BTreeMap<String, Vec<u16>>,
+}
+
+/// Talks to any device described by a `DeviceProfile`.
+#[derive(Debug)]
+pub struct ProfiledDevice {
+    pub unit_id: u8,
+    pub context: ThreadSafeContext,
+    pub profile: DeviceProfile,
+}
+
+impl ProfiledDevice {
+    pub fn new(unit_id: u8, context: ThreadSafeContext, profile: DeviceProfile) -> Self {
+        ProfiledDevice {
+            unit_id,
+            context,
+            profile,
+        }
+    }
+
+    pub async fn set_slave_id(&mut self) {
+        self.context.set_slave(Slave(self.unit_id)).await;
+    }
+
+    fn block(&self, name: &str) -> Result<RegisterBlock, ProfileError> {
+        self.profile
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ProfileError::UnknownBlock(name.to_string()))
+    }
+
+    pub async fn read(&mut self, name: &str) -> Result<Vec<Value>, ProfileError> {
+        let block = self.block(name)?;
+        let count = self.profile.count(&block);
+        self.read_range(&block, 0, count).await
+    }
+
+    pub async fn read_index(&mut self, name: &str, index: u16) -> Result<Value, ProfileError> {
+        let block = self.block(name)?;
+        self.check_range(&block, index, 1)?;
+        let values = self.read_range(&block, index, 1).await?;
+        values.into_iter().next().ok_or(ProfileError::OutOfRange {
+            block: block.name,
+            index,
+        })
+    }
+
+    pub async fn write_index(
+        &mut self,
+        name: &str,
+        index: u16,
+        value: Value,
+    ) -> Result<(), ProfileError> {
+        self.write(name, index, &[value]).await
+    }
+
+    /// Writes `values` to consecutive registers of the block from `start`.
+    pub async fn write(
+        &mut self,
+        name: &str,
+        start: u16,
+        values: &[Value],
+    ) -> Result<(), ProfileError> {
+        let block = self.block(name)?;
+        if !block.kind.is_writable() {
+            return Err(ProfileError::ReadOnly(block.name));
+        }
+        self.check_range(&block, start, values.len() as u16)?;
+        if let Some(value) = values.iter().find(|value| !block.is_valid(value)) {
+            return Err(ProfileError::InvalidValue {
+                block: block.name,
+                value: value.clone(),
+            });
+        }
+        let address = Self::address(&block, start, values.len() as u16)?;
+        let mut context = self.context.lock_slave(Slave(self.unit_id)).await;
+        match (block.kind, values) {
+            (RegisterKind::Coil, [Value::Bool(value)]) => context
+                .write_single_coil(address, *value)
+                .await
+                .map_err(ProfileError::ModbusError)?
+                .map_err(ProfileError::ModbusException)?,
+            (RegisterKind::Coil, values) => {
+                let coils: Vec<bool> = values
+                    .iter()
+                    .map(|value| matches!(value, Value::Bool(true)))
+                    .collect();
+                context
+                    .write_multiple_coils(address, &coils)
+                    .await
+                    .map_err(ProfileError::ModbusError)?
+                    .map_err(ProfileError::ModbusException)?
+            }
+            (_, [Value::U16(value)]) => context
+                .write_single_register(address, *value)
+                .await
+                .map_err(ProfileError::ModbusError)?
+                .map_err(ProfileError::ModbusException)?,
+            (_, values) => {
+                let words: Vec<u16> = values
+                    .iter()
+                    .map(|value| match value {
+                        Value::U16(value) => *value,
+                        Value::Bool(value) => *value as u16,
+                    })
+                    .collect();
+                context
+                    .write_multiple_registers(address, &words)
+                    .await
+                    .map_err(ProfileError::ModbusError)?
+                    .map_err(ProfileError::ModbusException)?
+            }
+        }
+        Ok(())
+    }
+
+    fn check_range(&self, block: &RegisterBlock, start: u16, len: u16) -> Result<(), ProfileError> {
+        let count = self.profile.count(block);
+        if len == 0 || start as u32 + len as u32 > count as u32 {
+            return Err(ProfileError::OutOfRange {
+                block: block.name.clone(),
+                index: start.saturating_add(len.saturating_sub(1)),
+            });
+        }
+        Ok(())
+    }
+
+    // A block sized by the channel count isn't known to fit until it is used
+    fn address(block: &RegisterBlock, start: u16, len: u16) -> Result<u16, ProfileError> {
+        block
+            .address
+            .checked_add(start)
+            .filter(|address| *address as u32 + len as u32 <= u16::MAX as u32 + 1)
+            .ok_or_else(|| ProfileError::OutOfRange {
+                block: block.name.clone(),
+                index: start.saturating_add(len.saturating_sub(1)),
+            })
+    }
+
+    async fn read_range(
+        &mut self,
+        block: &RegisterBlock,
+        start: u16,
+        count: u16,
+    ) -> Result<Vec<Value>, ProfileError> {
+        let address = Self::address(block, start, count)?;
+        let mut context = self.context.lock_slave(Slave(self.unit_id)).await;
+        let values = match block.kind {
+            RegisterKind::Coil => context
+                .read_coils(address, count)
+                .await
+                .map_err(ProfileError::ModbusError)?
+                .map_err(ProfileError::ModbusException)?
+                .into_iter()
+                .map(Value::Bool)
+                .collect(),
+            RegisterKind::DiscreteInput => context
+                .read_discrete_inputs(address, count)
+                .await
+                .map_err(ProfileError::ModbusError)?
+                .map_err(ProfileError::ModbusException)?
+                .into_iter()
+                .map(Value::Bool)
+                .collect(),
+            RegisterKind::HoldingRegister => context
+                .read_holding_registers(address, count)
+                .await
+                .map_err(ProfileError::ModbusError)?
+                .map_err(ProfileError::ModbusException)?
+                .into_iter()
+                .map(Value::U16)
+                .collect(),
+            RegisterKind::InputRegister => context
+                .read_input_registers(address, count)
+                .await
+                .map_err(ProfileError::ModbusError)?
+                .map_err(ProfileError::ModbusException)?
+                .into_iter()
+                .map(Value::U16)
+                .collect(),
+        };
+        Ok(values)
+    }
+
+    // Enum holding registers are the configuration, plain words are values or commands
+    fn configuration_blocks(&self) -> Vec<RegisterBlock> {
+        self.profile
+            .blocks
+            .iter()
+            .filter(|block| {
+                block.kind == RegisterKind::HoldingRegister
+                    && matches!(block.value_type, ValueType::Enum(_))
+            })
+            .cloned()
+            .collect()
+    }
+
+    async fn read_words(&mut self, block: &RegisterBlock) -> Result<Vec<u16>, ProfileError> {
+        let count = self.profile.count(block);
+        Ok(self
+            .read_range(block, 0, count)
+            .await?
+            .into_iter()
+            .map(|value| match value {
+                Value::U16(value) => value,
+                Value::Bool(value) => value as u16,
+            })
+            .collect())
+    }
+
+    pub async fn snapshot(&mut self) -> Result<ProfileSnapshot, ProfileError> {
+        let mut blocks = BTreeMap::new();
+        for block in self.configuration_blocks() {
+            let values = self.read_words(&block).await?;
+            blocks.insert(block.name, values);
+        }
+        Ok(ProfileSnapshot {
+            settings: DeviceSettings::read(self).await?,
+            blocks,
+        })
+    }
+
+    /// Writes and verifies every configuration block, then `restore_settings`.
+    pub async fn restore(&mut self, snapshot: &ProfileSnapshot) -> Result<(), ProfileError> {
+        for (name, values) in &snapshot.blocks {
+            let block = self.block(name)?;
+            let values: Vec<Value> = values.iter().map(|value| Value::U16(*value)).collect();
+            self.write(name, 0, &values).await?;
+            let read = self.read_words(&block).await?;
+            if read.iter().map(|value| Value::U16(*value)).ne(values) {
+                return Err(SettingsError::VerifyFailed(format!(
+                    "{} read back as {:?}",
+                    name, read
+                ))
+                .into());
+            }
+        }
+        self.restore_settings(&snapshot.settings).await
+    }
+}
+
+impl WaveshareModbus for ProfiledDevice {
+    type Error = ProfileError;
+
+    async fn set_uart_parameters(
+        &mut self,
+        baud: Baudrates,
+        parity: Parity,
+    ) -> Result<(), Self::Error> {
+        let value = ((parity as u16) << 8) | (baud as u16);
+        self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .write_single_register(CommonHoldingRegisters::UartParameters as u16, value)
+            .await
+            .map_err(ProfileError::ModbusError)?
+            .map_err(ProfileError::ModbusException)?;
+        Ok(())
+    }
+
+    async fn set_device_address(&mut self, address: u8) -> Result<(), Self::Error> {
+        self.context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .write_single_register(CommonHoldingRegisters::DeviceAddress as u16, address as u16)
+            .await
+            .map_err(ProfileError::ModbusError)?
+            .map_err(ProfileError::ModbusException)?;
+        Ok(())
+    }
+
+    async fn read_software_version(&mut self) -> Result<u16, Self::Error> {
+        let result = self
+            .context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_holding_registers(CommonHoldingRegisters::SoftwareVersion as u16, 1)
+            .await
+            .map_err(ProfileError::ModbusError)?
+            .map_err(ProfileError::ModbusException)?;
+        Ok(result[0])
+    }
+
+    async fn read_common_register(
+        &mut self,
+        register: CommonHoldingRegisters,
+    ) -> Result<u16, Self::Error> {
+        let result = self
+            .context
+            .lock_slave(Slave(self.unit_id))
+            .await
+            .read_holding_registers(register as u16, 1)
+            .await
+            .map_err(ProfileError::ModbusError)?
+            .map_err(ProfileError::ModbusException)?;
+        Ok(result[0])
+    }
+
+    fn set_unit_id(&mut self, unit_id: u8) {
+        self.unit_id = unit_id;
+    }
+}
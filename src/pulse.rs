@@ -0,0 +1,175 @@
+use crate::{
+    common::Channel,
+    digital::{flash_interval_units, Action, DigitalIO, DigitalIOError},
+};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+#[derive(Error, Debug)]
+pub enum PulseError {
+    #[error("Digital IO Error: `{0}`")]
+    DigitalIO(#[from] DigitalIOError),
+    #[error("Pulse task failed")]
+    TaskFailed,
+    #[error("A repeating pulse train needs a period greater than zero")]
+    InvalidPeriod,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PulseTrain {
+    // `None` repeats until stopped
+    pub count: Option<u32>,
+    pub on: Duration,
+    pub off: Duration,
+}
+
+impl PulseTrain {
+    pub fn new(count: u32, on: Duration, off: Duration) -> Self {
+        PulseTrain {
+            count: Some(count),
+            on,
+            off,
+        }
+    }
+
+    /// Slow host-timed PWM, `duty` is the fraction of `period` spent on.
+    pub fn pwm(period: Duration, duty: f64) -> Result<Self, PulseError> {
+        if period.is_zero() {
+            return Err(PulseError::InvalidPeriod);
+        }
+        let on = period.mul_f64(duty.clamp(0.0, 1.0));
+        Ok(PulseTrain {
+            count: None,
+            on,
+            off: period.saturating_sub(on),
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PulseOutcome {
+    Completed { pulses: u32 },
+    Stopped { pulses: u32 },
+}
+
+/// Dropping the handle stops the pulses and turns the output off.
+#[derive(Debug)]
+pub struct PulseHandle {
+    stop: Option<oneshot::Sender<()>>,
+    task: JoinHandle<Result<PulseOutcome, PulseError>>,
+}
+
+impl PulseHandle {
+    pub fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+
+    pub async fn finished(self) -> Result<PulseOutcome, PulseError> {
+        // Keep the stop sender alive, dropping it would stop the pulses
+        let PulseHandle { stop: _stop, task } = self;
+        task.await.map_err(|_| PulseError::TaskFailed)?
+    }
+}
+
+pub fn pulse_train<const N: usize>(
+    io: &DigitalIO<N>,
+    channel: Channel,
+    train: PulseTrain,
+) -> Result<PulseHandle, PulseError> {
+    if train.count.is_none() && (train.on + train.off).is_zero() {
+        return Err(PulseError::InvalidPeriod);
+    }
+    let io = DigitalIO::<N>::with_channels(io.unit_id, io.context.clone());
+    Ok(spawn(move |stopped| run_train(io, channel, train, stopped)))
+}
+
+/// Uses the device flash register when `duration` is a whole number of
+/// `FLASH_INTERVAL_UNIT`s, otherwise times the pulse on the host.
+pub fn one_shot<const N: usize>(
+    io: &DigitalIO<N>,
+    channel: Channel,
+    duration: Duration,
+) -> PulseHandle {
+    let io = DigitalIO::<N>::with_channels(io.unit_id, io.context.clone());
+    if flash_interval_units(duration).is_err() {
+        let train = PulseTrain::new(1, duration, Duration::ZERO);
+        return spawn(move |stopped| run_train(io, channel, train, stopped));
+    }
+    spawn(move |mut stopped| async move {
+        let mut io = io;
+        io.flash_output_on(channel, duration).await?;
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => Ok(PulseOutcome::Completed { pulses: 1 }),
+            _ = &mut stopped => {
+                io.write_output_channel(channel, Action::Off).await?;
+                Ok(PulseOutcome::Stopped { pulses: 1 })
+            }
+        }
+    })
+}
+
+fn spawn<F, Fut>(run: F) -> PulseHandle
+where
+    F: FnOnce(oneshot::Receiver<()>) -> Fut,
+    Fut: std::future::Future<Output = Result<PulseOutcome, PulseError>> + Send + 'static,
+{
+    let (stop, stopped) = oneshot::channel();
+    PulseHandle {
+        stop: Some(stop),
+        task: tokio::spawn(run(stopped)),
+    }
+}
+
+// Zero length phases are skipped and only changes are written, so 0% and
+// 100% duty hold the output instead of glitching it every period
+async fn run_train<const N: usize>(
+    mut io: DigitalIO<N>,
+    channel: Channel,
+    train: PulseTrain,
+    mut stopped: oneshot::Receiver<()>,
+) -> Result<PulseOutcome, PulseError> {
+    let mut level = None;
+    let mut pulses = 0;
+    while train.count.is_none_or(|count| pulses < count) {
+        pulses += 1;
+        if !train.on.is_zero() {
+            set_level(&mut io, channel, &mut level, Action::On).await?;
+            tokio::select! {
+                _ = tokio::time::sleep(train.on) => {}
+                _ = &mut stopped => {
+                    set_level(&mut io, channel, &mut level, Action::Off).await?;
+                    return Ok(PulseOutcome::Stopped { pulses });
+                }
+            }
+        }
+        if train.count == Some(pulses) {
+            break;
+        }
+        if !train.off.is_zero() {
+            set_level(&mut io, channel, &mut level, Action::Off).await?;
+            tokio::select! {
+                _ = tokio::time::sleep(train.off) => {}
+                _ = &mut stopped => return Ok(PulseOutcome::Stopped { pulses }),
+            }
+        }
+    }
+    set_level(&mut io, channel, &mut level, Action::Off).await?;
+    Ok(PulseOutcome::Completed { pulses })
+}
+
+async fn set_level<const N: usize>(
+    io: &mut DigitalIO<N>,
+    channel: Channel,
+    level: &mut Option<Action>,
+    action: Action,
+) -> Result<(), PulseError> {
+    if *level != Some(action) {
+        io.write_output_channel(channel, action).await?;
+        *level = Some(action);
+    }
+    Ok(())
+}
@@ -0,0 +1,287 @@
+use crate::{
+    analog_out::{AnalogOutput, AnalogOutputError},
+    common::Channel,
+};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Instant, MissedTickBehavior};
+
+#[derive(Error, Debug)]
+pub enum RampError {
+    #[error("Ramp task has stopped")]
+    Stopped,
+    #[error("Analog Output Error: `{0}`")]
+    AnalogOutput(#[from] AnalogOutputError),
+    #[error("Ramp Write Failed: `{0}`")]
+    WriteFailed(String),
+    #[error("Update period must be greater than zero")]
+    InvalidUpdatePeriod,
+    #[error("Invalid Ramp Rate: `{0:?}`")]
+    InvalidRate(RampRate),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RampRate {
+    // Raw units per second
+    PerSecond(f64),
+    Over(Duration),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RampOutcome {
+    Completed,
+    Cancelled,
+    // Another ramp was started on the same channel
+    Superseded,
+}
+
+#[derive(Debug)]
+enum Command {
+    Start {
+        channel: Channel,
+        target: u16,
+        rate: RampRate,
+        id: u64,
+        done: oneshot::Sender<Result<RampOutcome, RampError>>,
+    },
+    Cancel {
+        channel: Channel,
+        id: Option<u64>,
+    },
+}
+
+#[derive(Debug)]
+struct ActiveRamp {
+    id: u64,
+    from: f64,
+    target: u16,
+    started: Instant,
+    duration: Duration,
+    current: u16,
+    done: oneshot::Sender<Result<RampOutcome, RampError>>,
+}
+
+impl ActiveRamp {
+    fn value_at(&self, now: Instant) -> u16 {
+        let elapsed = now.saturating_duration_since(self.started);
+        let fraction = if self.duration.is_zero() {
+            1.0
+        } else {
+            (elapsed.as_secs_f64() / self.duration.as_secs_f64()).min(1.0)
+        };
+        (self.from + (self.target as f64 - self.from) * fraction).round() as u16
+    }
+}
+
+#[derive(Debug)]
+pub struct RampHandle {
+    pub channel: Channel,
+    id: u64,
+    commands: mpsc::UnboundedSender<Command>,
+    done: oneshot::Receiver<Result<RampOutcome, RampError>>,
+}
+
+impl RampHandle {
+    pub fn cancel(&self) {
+        let _ = self.commands.send(Command::Cancel {
+            channel: self.channel,
+            id: Some(self.id),
+        });
+    }
+
+    pub async fn finished(self) -> Result<RampOutcome, RampError> {
+        self.done.await.map_err(|_| RampError::Stopped)?
+    }
+}
+
+/// Moves analog outputs towards their targets, sharing one write per update
+/// across every channel that is ramping.
+#[derive(Debug, Clone)]
+pub struct Ramper {
+    commands: mpsc::UnboundedSender<Command>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Ramper {
+    pub fn spawn(output: AnalogOutput, update_period: Duration) -> Result<Self, RampError> {
+        if update_period.is_zero() {
+            return Err(RampError::InvalidUpdatePeriod);
+        }
+        let (commands, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(output, update_period, receiver));
+        Ok(Ramper {
+            commands,
+            next_id: Default::default(),
+        })
+    }
+
+    pub fn ramp(&self, channel: Channel, target: u16, rate: RampRate) -> RampHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, done) = oneshot::channel();
+        let command = Command::Start {
+            channel,
+            target,
+            rate,
+            id,
+            done: sender,
+        };
+        // If the task has stopped the handle resolves to `RampError::Stopped`
+        let _ = self.commands.send(command);
+        RampHandle {
+            channel,
+            id,
+            commands: self.commands.clone(),
+            done,
+        }
+    }
+
+    pub fn cancel(&self, channel: Channel) {
+        let _ = self.commands.send(Command::Cancel { channel, id: None });
+    }
+}
+
+async fn run(
+    mut output: AnalogOutput,
+    update_period: Duration,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+) {
+    let mut ramps: BTreeMap<u16, ActiveRamp> = BTreeMap::new();
+    // Set once every sender is gone, the running ramps are still finished
+    let mut closed = false;
+    let mut interval = tokio::time::interval(update_period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            command = commands.recv(), if !closed => match command {
+                Some(command) => handle_command(&mut output, &mut ramps, command).await,
+                None => closed = true,
+            },
+            _ = interval.tick(), if !ramps.is_empty() => update(&mut output, &mut ramps).await,
+        }
+        if closed && ramps.is_empty() {
+            return;
+        }
+    }
+}
+
+async fn handle_command(
+    output: &mut AnalogOutput,
+    ramps: &mut BTreeMap<u16, ActiveRamp>,
+    command: Command,
+) {
+    match command {
+        Command::Start {
+            channel,
+            target,
+            rate,
+            id,
+            done,
+        } => {
+            let from = match ramps.remove(&(channel as u16)) {
+                Some(previous) => {
+                    let _ = previous.done.send(Ok(RampOutcome::Superseded));
+                    previous.current
+                }
+                None => match output.read_output_channel_value(channel).await {
+                    Ok(value) => value,
+                    Err(err) => {
+                        let _ = done.send(Err(err.into()));
+                        return;
+                    }
+                },
+            };
+            let duration = match rate {
+                RampRate::Over(duration) => duration,
+                RampRate::PerSecond(per_second) => {
+                    // A rate that would step the output at once, or one small
+                    // enough to overflow a Duration, is refused
+                    let span = (target as f64 - from as f64).abs();
+                    let duration = (per_second.is_finite() && per_second > 0.0)
+                        .then(|| Duration::try_from_secs_f64(span / per_second).ok())
+                        .flatten();
+                    match duration {
+                        Some(duration) => duration,
+                        None => {
+                            let _ = done.send(Err(RampError::InvalidRate(rate)));
+                            return;
+                        }
+                    }
+                }
+            };
+            ramps.insert(
+                channel as u16,
+                ActiveRamp {
+                    id,
+                    from: from as f64,
+                    target,
+                    started: Instant::now(),
+                    duration,
+                    current: from,
+                    done,
+                },
+            );
+        }
+        Command::Cancel { channel, id } => {
+            let matches = ramps
+                .get(&(channel as u16))
+                .is_some_and(|ramp| id.is_none_or(|id| id == ramp.id));
+            if matches {
+                if let Some(ramp) = ramps.remove(&(channel as u16)) {
+                    let _ = ramp.done.send(Ok(RampOutcome::Cancelled));
+                }
+            }
+        }
+    }
+}
+
+async fn update(output: &mut AnalogOutput, ramps: &mut BTreeMap<u16, ActiveRamp>) {
+    let now = Instant::now();
+    for ramp in ramps.values_mut() {
+        ramp.current = ramp.value_at(now);
+    }
+
+    // Channels next to each other are written with a single request
+    let mut runs: Vec<(u16, Vec<u16>)> = Vec::new();
+    for (channel, ramp) in ramps.iter() {
+        match runs.last_mut() {
+            Some((start, values)) if *start + values.len() as u16 == *channel => {
+                values.push(ramp.current)
+            }
+            _ => runs.push((*channel, vec![ramp.current])),
+        }
+    }
+
+    for (start, values) in runs {
+        let channels = start..start + values.len() as u16;
+        let result = match Channel::try_from(start as u8) {
+            Ok(channel) => output.write_output_channel_values(channel, &values).await,
+            Err(_) => continue,
+        };
+        match result {
+            Ok(()) => {
+                for channel in channels {
+                    let finished = ramps
+                        .get(&channel)
+                        .is_some_and(|ramp| ramp.current == ramp.target);
+                    if finished {
+                        if let Some(ramp) = ramps.remove(&channel) {
+                            let _ = ramp.done.send(Ok(RampOutcome::Completed));
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                let message = err.to_string();
+                for channel in channels {
+                    if let Some(ramp) = ramps.remove(&channel) {
+                        let _ = ramp.done.send(Err(RampError::WriteFailed(message.clone())));
+                    }
+                }
+            }
+        }
+    }
+}
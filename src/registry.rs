@@ -0,0 +1,245 @@
+use crate::{
+    analog_in::{AnalogInput, AnalogInputError},
+    analog_out::{AnalogOutput, AnalogOutputError},
+    common::Channel,
+    digital::{Action, AnyDigitalIO, DigitalIOError},
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("Unknown Tag: `{0}`")]
+    UnknownTag(String),
+    #[error("Unknown Device: `{0}`")]
+    UnknownDevice(String),
+    #[error("Tag `{0}` is not supported by its device")]
+    UnsupportedTag(String),
+    #[error("Tag `{0}` is read only")]
+    ReadOnly(String),
+    #[error("Type mismatch for tag `{0}`")]
+    TypeMismatch(String),
+    #[error("Digital IO Error: `{0}`")]
+    DigitalIO(#[from] DigitalIOError),
+    #[error("Analog Input Error: `{0}`")]
+    AnalogInput(#[from] AnalogInputError),
+    #[error("Analog Output Error: `{0}`")]
+    AnalogOutput(#[from] AnalogOutputError),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TagType {
+    Digital,
+    Analog,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TagValue {
+    Digital(bool),
+    Analog(u16),
+}
+
+impl TagValue {
+    pub fn tag_type(&self) -> TagType {
+        match self {
+            TagValue::Digital(_) => TagType::Digital,
+            TagValue::Analog(_) => TagType::Analog,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TagValue::Digital(value) => Some(*value),
+            TagValue::Analog(_) => None,
+        }
+    }
+
+    pub fn as_u16(&self) -> Option<u16> {
+        match self {
+            TagValue::Digital(_) => None,
+            TagValue::Analog(value) => Some(*value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagDefinition {
+    pub device: String,
+    pub channel: Channel,
+    pub direction: Direction,
+    pub tag_type: TagType,
+}
+
+impl TagDefinition {
+    pub fn new(device: &str, channel: Channel, direction: Direction, tag_type: TagType) -> Self {
+        TagDefinition {
+            device: device.to_string(),
+            channel,
+            direction,
+            tag_type,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Device {
+    DigitalIO(AnyDigitalIO),
+    AnalogInput(AnalogInput),
+    AnalogOutput(AnalogOutput),
+}
+
+impl Device {
+    fn supports(&self, direction: Direction, tag_type: TagType) -> bool {
+        matches!(
+            (self, direction, tag_type),
+            (Device::DigitalIO(_), _, TagType::Digital)
+                | (Device::AnalogInput(_), Direction::Input, TagType::Analog)
+                | (Device::AnalogOutput(_), Direction::Output, TagType::Analog)
+        )
+    }
+
+    // Reads the whole bank for a direction in a single transaction
+    async fn read_bank(&mut self, direction: Direction) -> Result<Vec<TagValue>, RegistryError> {
+        let values = match (self, direction) {
+            (Device::DigitalIO(io), Direction::Input) => io
+                .read_input_channels()
+                .await?
+                .into_iter()
+                .map(TagValue::Digital)
+                .collect(),
+            (Device::DigitalIO(io), Direction::Output) => io
+                .read_output_channels()
+                .await?
+                .into_iter()
+                .map(TagValue::Digital)
+                .collect(),
+            (Device::AnalogInput(input), _) => input
+                .read_input_channels()
+                .await?
+                .into_iter()
+                .map(TagValue::Analog)
+                .collect(),
+            (Device::AnalogOutput(output), _) => output
+                .read_output_channels()
+                .await?
+                .into_iter()
+                .map(TagValue::Analog)
+                .collect(),
+        };
+        Ok(values)
+    }
+}
+
+/// Maps string tags such as `pump1.run` onto device channels.
+#[derive(Debug, Default)]
+pub struct TagRegistry {
+    devices: HashMap<String, Device>,
+    tags: HashMap<String, TagDefinition>,
+}
+
+impl TagRegistry {
+    pub fn new() -> Self {
+        TagRegistry::default()
+    }
+
+    pub fn add_device(&mut self, name: &str, device: Device) {
+        self.devices.insert(name.to_string(), device);
+    }
+
+    pub fn add_tag(&mut self, name: &str, definition: TagDefinition) -> Result<(), RegistryError> {
+        let device = self
+            .devices
+            .get(&definition.device)
+            .ok_or_else(|| RegistryError::UnknownDevice(definition.device.clone()))?;
+        if !device.supports(definition.direction, definition.tag_type) {
+            return Err(RegistryError::UnsupportedTag(name.to_string()));
+        }
+        self.tags.insert(name.to_string(), definition);
+        Ok(())
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&TagDefinition> {
+        self.tags.get(name)
+    }
+
+    pub fn device_mut(&mut self, name: &str) -> Option<&mut Device> {
+        self.devices.get_mut(name)
+    }
+
+    pub async fn read_tag(&mut self, name: &str) -> Result<TagValue, RegistryError> {
+        let mut values = self.read_tags(&[name]).await?;
+        values
+            .remove(name)
+            .ok_or_else(|| RegistryError::UnknownTag(name.to_string()))
+    }
+
+    /// Reads several tags, issuing one transaction per device and direction.
+    pub async fn read_tags(
+        &mut self,
+        names: &[&str],
+    ) -> Result<HashMap<String, TagValue>, RegistryError> {
+        let mut groups: HashMap<(&str, Direction), Vec<(&str, Channel)>> = HashMap::new();
+        for name in names {
+            let definition = self
+                .tags
+                .get(*name)
+                .ok_or_else(|| RegistryError::UnknownTag(name.to_string()))?;
+            groups
+                .entry((definition.device.as_str(), definition.direction))
+                .or_default()
+                .push((name, definition.channel));
+        }
+
+        let mut results = HashMap::new();
+        for ((device_name, direction), members) in groups {
+            let device = self
+                .devices
+                .get_mut(device_name)
+                .ok_or_else(|| RegistryError::UnknownDevice(device_name.to_string()))?;
+            let bank = device.read_bank(direction).await?;
+            for (name, channel) in members {
+                let value = bank
+                    .get(channel as usize)
+                    .copied()
+                    .ok_or_else(|| RegistryError::UnsupportedTag(name.to_string()))?;
+                results.insert(name.to_string(), value);
+            }
+        }
+        Ok(results)
+    }
+
+    pub async fn write_tag(&mut self, name: &str, value: TagValue) -> Result<(), RegistryError> {
+        let definition = self
+            .tags
+            .get(name)
+            .ok_or_else(|| RegistryError::UnknownTag(name.to_string()))?;
+        if definition.direction != Direction::Output {
+            return Err(RegistryError::ReadOnly(name.to_string()));
+        }
+        if definition.tag_type != value.tag_type() {
+            return Err(RegistryError::TypeMismatch(name.to_string()));
+        }
+        let channel = definition.channel;
+        let device = self
+            .devices
+            .get_mut(&definition.device)
+            .ok_or_else(|| RegistryError::UnknownDevice(definition.device.clone()))?;
+        match (device, value) {
+            (Device::DigitalIO(io), TagValue::Digital(state)) => {
+                let action = if state { Action::On } else { Action::Off };
+                io.write_output_channel(channel, action).await?;
+            }
+            (Device::AnalogOutput(output), TagValue::Analog(raw)) => {
+                output.write_output_channel_value(channel, raw).await?;
+            }
+            _ => return Err(RegistryError::UnsupportedTag(name.to_string())),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{
+    common::{Baudrates, Channel, DeviceSettings, Parity, UartParameters, WaveshareModbus},
+    digital::{Action, DigitalIO, DigitalIOError, DigitalIOSnapshot, IoBank},
+    ThreadSafeContext,
+};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RelayError {
+    #[error("Relay board has no inputs")]
+    NoInputs,
+    #[error("Digital IO Error: `{0}`")]
+    DigitalIO(#[from] DigitalIOError),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RelayModel {
+    Standard,
+    // The (D) variant with isolated digital inputs
+    WithInputs,
+}
+
+/// Waveshare Modbus RTU Relay board.
+///
+/// The relay coil map matches the IO module, so requests go through `DigitalIO`.
+#[derive(Debug)]
+pub struct Relay<const N: usize = 8> {
+    pub model: RelayModel,
+    io: DigitalIO<N>,
+}
+
+impl Relay {
+    pub fn new(unit_id: u8, context: ThreadSafeContext, model: RelayModel) -> Self {
+        Relay::with_channels(unit_id, context, model)
+    }
+}
+
+impl<const N: usize> Relay<N> {
+    pub fn with_channels(unit_id: u8, context: ThreadSafeContext, model: RelayModel) -> Self {
+        Relay {
+            model,
+            io: DigitalIO::with_channels(unit_id, context),
+        }
+    }
+
+    pub fn unit_id(&self) -> u8 {
+        self.io.unit_id
+    }
+
+    pub fn io(&mut self) -> &mut DigitalIO<N> {
+        &mut self.io
+    }
+
+    pub async fn write_relay(
+        &mut self,
+        channel: Channel,
+        action: Action,
+    ) -> Result<(), RelayError> {
+        Ok(self.io.write_output_channel(channel, action).await?)
+    }
+
+    pub async fn write_relays(&mut self, actions: [Action; N]) -> Result<(), RelayError> {
+        Ok(self.io.write_output_channels(actions).await?)
+    }
+
+    pub async fn write_all_relays(&mut self, action: Action) -> Result<(), RelayError> {
+        match action {
+            Action::On => self.io.open_all_outputs().await?,
+            Action::Off => self.io.close_all_outputs().await?,
+        }
+        Ok(())
+    }
+
+    // The board's own toggle value (0x5500) isn't a valid coil value for
+    // tokio-modbus, so toggling reads and writes back while holding the bus
+    pub async fn toggle_relay(&mut self, channel: Channel) -> Result<bool, RelayError> {
+        if channel as usize >= N {
+            return Err(DigitalIOError::InvalidChannel(channel).into());
+        }
+        let (_, after) = self
+            .io
+            .modify_output_channels(|mut bank| {
+                bank[channel] = !bank[channel];
+                bank
+            })
+            .await?;
+        Ok(after[channel])
+    }
+
+    pub async fn toggle_all_relays(&mut self) -> Result<IoBank<N>, RelayError> {
+        let (_, after) = self.io.modify_output_channels(|bank| !bank).await?;
+        Ok(after)
+    }
+
+    pub async fn flash_relay_on(
+        &mut self,
+        channel: Channel,
+        interval: Duration,
+    ) -> Result<(), RelayError> {
+        Ok(self.io.flash_output_on(channel, interval).await?)
+    }
+
+    pub async fn flash_relay_off(
+        &mut self,
+        channel: Channel,
+        interval: Duration,
+    ) -> Result<(), RelayError> {
+        Ok(self.io.flash_output_off(channel, interval).await?)
+    }
+
+    pub async fn read_relay_status(&mut self, channel: Channel) -> Result<bool, RelayError> {
+        Ok(self.io.read_output_channel_status(channel).await?)
+    }
+
+    pub async fn read_relays(&mut self) -> Result<IoBank<N>, RelayError> {
+        Ok(self.io.read_output_bank().await?)
+    }
+
+    pub async fn read_input_status(&mut self, channel: Channel) -> Result<bool, RelayError> {
+        self.check_inputs()?;
+        Ok(self.io.read_input_channel_status(channel).await?)
+    }
+
+    pub async fn read_inputs(&mut self) -> Result<IoBank<N>, RelayError> {
+        self.check_inputs()?;
+        Ok(self.io.read_input_bank().await?)
+    }
+
+    // Only the boards with inputs have control mode registers
+    pub async fn snapshot(&mut self) -> Result<DigitalIOSnapshot, RelayError> {
+        match self.model {
+            RelayModel::Standard => Ok(DigitalIOSnapshot {
+                settings: DeviceSettings::read(&mut self.io).await?,
+                control_modes: Vec::new(),
+            }),
+            RelayModel::WithInputs => Ok(self.io.snapshot().await?),
+        }
+    }
+
+    pub async fn restore(&mut self, snapshot: &DigitalIOSnapshot) -> Result<(), RelayError> {
+        Ok(self.io.restore(snapshot).await?)
+    }
+
+    fn check_inputs(&self) -> Result<(), RelayError> {
+        match self.model {
+            RelayModel::Standard => Err(RelayError::NoInputs),
+            RelayModel::WithInputs => Ok(()),
+        }
+    }
+}
+
+impl<const N: usize> WaveshareModbus for Relay<N> {
+    type Error = RelayError;
+
+    async fn set_uart_parameters(
+        &mut self,
+        baudrate: Baudrates,
+        parity: Parity,
+    ) -> Result<(), Self::Error> {
+        Ok(self.io.set_uart_parameters(baudrate, parity).await?)
+    }
+
+    async fn set_device_address(&mut self, address: u8) -> Result<(), Self::Error> {
+        Ok(self.io.set_device_address(address).await?)
+    }
+
+    async fn read_software_version(&mut self) -> Result<u16, Self::Error> {
+        Ok(self.io.read_software_version().await?)
+    }
+
+    async fn read_uart_parameters(&mut self) -> Result<UartParameters, Self::Error> {
+        Ok(self.io.read_uart_parameters().await?)
+    }
+
+    async fn read_device_address(&mut self) -> Result<u8, Self::Error> {
+        Ok(self.io.read_device_address().await?)
+    }
+}
@@ -0,0 +1,177 @@
+use crate::common::{Baudrates, Parity, UartParameters};
+use crate::ThreadSafeContext;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+use tokio_serial::{
+    DataBits, SerialPort, SerialPortBuilder, SerialPortBuilderExt, SerialStream, StopBits,
+};
+
+impl From<Parity> for tokio_serial::Parity {
+    fn from(parity: Parity) -> Self {
+        match parity {
+            Parity::None => tokio_serial::Parity::None,
+            Parity::Even => tokio_serial::Parity::Even,
+            Parity::Odd => tokio_serial::Parity::Odd,
+        }
+    }
+}
+
+/// Port settings matching what `set_uart_parameters` configures on a module.
+///
+/// The modules always use 8 data bits and 1 stop bit.
+pub fn port_builder(path: &str, baudrate: Baudrates, parity: Parity) -> SerialPortBuilder {
+    tokio_serial::new(path, baudrate.bps())
+        .data_bits(DataBits::Eight)
+        .parity(parity.into())
+        .stop_bits(StopBits::One)
+}
+
+impl UartParameters {
+    pub fn port_builder(&self, path: &str) -> SerialPortBuilder {
+        port_builder(path, self.baudrate, self.parity)
+    }
+}
+
+/// Level of RTS while transmitting, for RS485 adapters that switch the
+/// driver direction from RTS rather than in hardware.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RtsControl {
+    ActiveHigh,
+    ActiveLow,
+}
+
+#[derive(Debug, Clone)]
+pub struct SerialConfig {
+    pub path: String,
+    pub baudrate: Baudrates,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    // Applied to the port and as the response timeout of the context
+    pub timeout: Duration,
+    pub rts_control: Option<RtsControl>,
+}
+
+impl SerialConfig {
+    pub fn new(path: &str, baudrate: Baudrates, parity: Parity) -> Self {
+        SerialConfig {
+            path: path.to_string(),
+            baudrate,
+            parity,
+            stop_bits: StopBits::One,
+            timeout: Duration::from_secs(1),
+            rts_control: None,
+        }
+    }
+
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn rts_control(mut self, rts_control: RtsControl) -> Self {
+        self.rts_control = Some(rts_control);
+        self
+    }
+
+    // Start bit, 8 data bits, parity and stop bits
+    fn char_time(&self) -> Duration {
+        let bits = 9
+            + u32::from(self.parity != Parity::None)
+            + match self.stop_bits {
+                StopBits::One => 1,
+                StopBits::Two => 2,
+            };
+        Duration::from_secs_f64(f64::from(bits) / f64::from(self.baudrate.bps()))
+    }
+}
+
+/// Opens the port and returns a context ready to hand to the drivers.
+pub fn open(config: &SerialConfig) -> Result<ThreadSafeContext, tokio_serial::Error> {
+    let mut port = port_builder(&config.path, config.baudrate, config.parity)
+        .stop_bits(config.stop_bits)
+        .timeout(config.timeout)
+        .open_native_async()?;
+    let context = match config.rts_control {
+        Some(rts_control) => {
+            port.write_request_to_send(rts_control == RtsControl::ActiveLow)?;
+            tokio_modbus::client::rtu::attach(RtsStream {
+                port,
+                transmit_level: rts_control == RtsControl::ActiveHigh,
+                char_time: config.char_time(),
+                pending: 0,
+                drain: None,
+            })
+        }
+        None => tokio_modbus::client::rtu::attach(port),
+    };
+    Ok(ThreadSafeContext::new_with_baudrate(context, config.baudrate).with_timeout(config.timeout))
+}
+
+// Holds RTS at the transmit level from the first write of a frame until the
+// flushed bytes have had time to leave the UART, then releases the bus
+#[derive(Debug)]
+struct RtsStream {
+    port: SerialStream,
+    transmit_level: bool,
+    char_time: Duration,
+    pending: usize,
+    drain: Option<Pin<Box<Sleep>>>,
+}
+
+impl AsyncRead for RtsStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.port).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for RtsStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.pending == 0 {
+            this.port.write_request_to_send(this.transmit_level)?;
+        }
+        let written = ready!(Pin::new(&mut this.port).poll_write(cx, buf))?;
+        this.pending += written;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        ready!(Pin::new(&mut this.port).poll_flush(cx))?;
+        if this.pending == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let drain_time = this.char_time * this.pending as u32;
+        let drain = this
+            .drain
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(drain_time)));
+        ready!(drain.as_mut().poll(cx));
+        this.drain = None;
+        this.pending = 0;
+        this.port.write_request_to_send(!this.transmit_level)?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Pin::new(&mut self.port).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{
+    analog_out::AnalogOutput,
+    common::Channel,
+    digital::{AnyDigitalIO, DigitalIO, IoBank, Width},
+    poller::{Quality, Snapshot},
+    ThreadSafeContext,
+};
+use std::fmt;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+
+#[derive(Error, Debug)]
+pub enum WatchdogError {
+    #[error("Check period must be greater than zero")]
+    InvalidCheckPeriod,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SafeAction {
+    // Bits above the width of the board are ignored
+    DigitalOutputs {
+        unit_id: u8,
+        width: Width,
+        outputs: IoBank<32>,
+    },
+    CloseAllOutputs {
+        unit_id: u8,
+    },
+    AnalogValue {
+        unit_id: u8,
+        channel: Channel,
+        value: u16,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TripReason {
+    HeartbeatTimeout(Duration),
+    // Every `WatchdogHandle` was dropped
+    HeartbeatLost,
+    BusErrors {
+        consecutive: u32,
+        last_error: String,
+    },
+}
+
+impl fmt::Display for TripReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TripReason::HeartbeatTimeout(since) => {
+                write!(f, "no heartbeat for {:?}", since)
+            }
+            TripReason::HeartbeatLost => write!(f, "heartbeat source dropped"),
+            TripReason::BusErrors {
+                consecutive,
+                last_error,
+            } => write!(
+                f,
+                "{} consecutive bus errors, last: {}",
+                consecutive, last_error
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchdogState {
+    Armed,
+    Tripped(TripReason),
+    // Every safe action has been written
+    SafeStateApplied(TripReason),
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    pub heartbeat_timeout: Duration,
+    pub max_consecutive_bus_errors: u32,
+    pub check_period: Duration,
+    pub safe_state: Vec<SafeAction>,
+}
+
+#[derive(Debug)]
+enum Report {
+    Heartbeat,
+    BusOk,
+    BusError(String),
+}
+
+/// Feeds the watchdog from the application.
+#[derive(Debug, Clone)]
+pub struct WatchdogHandle {
+    reports: mpsc::UnboundedSender<Report>,
+    state: watch::Receiver<WatchdogState>,
+}
+
+impl WatchdogHandle {
+    pub fn beat(&self) {
+        let _ = self.reports.send(Report::Heartbeat);
+    }
+
+    pub fn report_bus_ok(&self) {
+        let _ = self.reports.send(Report::BusOk);
+    }
+
+    pub fn report_bus_error(&self, error: impl fmt::Display) {
+        let _ = self.reports.send(Report::BusError(error.to_string()));
+    }
+
+    pub fn state(&self) -> watch::Receiver<WatchdogState> {
+        self.state.clone()
+    }
+
+    /// Reports the quality of each poller scan as bus health.
+    ///
+    /// The monitor doesn't count as a heartbeat source, dropping every handle
+    /// still trips the watchdog while it runs.
+    pub fn monitor_scan(&self, mut scan: watch::Receiver<Snapshot>) -> JoinHandle<()> {
+        let reports = self.reports.downgrade();
+        tokio::spawn(async move {
+            while scan.changed().await.is_ok() {
+                // Checked on every scan, not only those that report something
+                let Some(reports) = reports.upgrade() else {
+                    return;
+                };
+                let snapshot = scan.borrow_and_update().clone();
+                let report = match snapshot.quality {
+                    Quality::Good => Report::BusOk,
+                    Quality::Bad => Report::BusError(
+                        snapshot
+                            .last_error
+                            .unwrap_or_else(|| "scan failed".to_string()),
+                    ),
+                    Quality::Uncertain => continue,
+                };
+                if reports.send(report).is_err() {
+                    return;
+                }
+            }
+        })
+    }
+}
+
+/// Drives outputs to a safe state when heartbeats stop or bus errors persist.
+#[derive(Debug)]
+pub struct Watchdog {
+    context: ThreadSafeContext,
+    config: WatchdogConfig,
+}
+
+impl Watchdog {
+    pub fn new(context: ThreadSafeContext, config: WatchdogConfig) -> Result<Self, WatchdogError> {
+        if config.check_period.is_zero() {
+            return Err(WatchdogError::InvalidCheckPeriod);
+        }
+        Ok(Watchdog { context, config })
+    }
+
+    /// The task finishes once the safe state has been applied.
+    pub fn spawn(self) -> (WatchdogHandle, JoinHandle<TripReason>) {
+        let (reports, receiver) = mpsc::unbounded_channel();
+        let (state_sender, state) = watch::channel(WatchdogState::Armed);
+        let task = tokio::spawn(self.run(receiver, state_sender));
+        (WatchdogHandle { reports, state }, task)
+    }
+
+    async fn run(
+        self,
+        mut reports: mpsc::UnboundedReceiver<Report>,
+        state: watch::Sender<WatchdogState>,
+    ) -> TripReason {
+        let mut last_heartbeat = Instant::now();
+        let mut consecutive_errors = 0;
+        let mut interval = tokio::time::interval(self.config.check_period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let reason = loop {
+            tokio::select! {
+                report = reports.recv() => match report {
+                    Some(Report::Heartbeat) => last_heartbeat = Instant::now(),
+                    Some(Report::BusOk) => consecutive_errors = 0,
+                    Some(Report::BusError(last_error)) => {
+                        consecutive_errors += 1;
+                        if consecutive_errors >= self.config.max_consecutive_bus_errors {
+                            break TripReason::BusErrors {
+                                consecutive: consecutive_errors,
+                                last_error,
+                            };
+                        }
+                    }
+                    None => break TripReason::HeartbeatLost,
+                },
+                _ = interval.tick() => {
+                    let since = last_heartbeat.elapsed();
+                    if since > self.config.heartbeat_timeout {
+                        break TripReason::HeartbeatTimeout(since);
+                    }
+                }
+            }
+        };
+
+        log::error!("watchdog tripped: {}", reason);
+        let _ = state.send(WatchdogState::Tripped(reason.clone()));
+
+        // Keep retrying until every output has reached its safe state
+        let mut pending = self.config.safe_state.clone();
+        loop {
+            let mut failed = Vec::new();
+            for action in pending {
+                if let Err(err) = self.apply(&action).await {
+                    log::warn!("watchdog failed to apply {:?}: {}", action, err);
+                    failed.push(action);
+                }
+            }
+            if failed.is_empty() {
+                break;
+            }
+            pending = failed;
+            interval.tick().await;
+        }
+
+        log::info!("watchdog applied safe state after: {}", reason);
+        let _ = state.send(WatchdogState::SafeStateApplied(reason.clone()));
+        reason
+    }
+
+    async fn apply(&self, action: &SafeAction) -> Result<(), String> {
+        match *action {
+            SafeAction::DigitalOutputs {
+                unit_id,
+                width,
+                outputs,
+            } => AnyDigitalIO::new(unit_id, self.context.clone(), width)
+                .write_output_bank(outputs)
+                .await
+                .map_err(|err| err.to_string()),
+            // The control all coil is the same on every width
+            SafeAction::CloseAllOutputs { unit_id } => {
+                DigitalIO::new(unit_id, self.context.clone())
+                    .close_all_outputs()
+                    .await
+                    .map_err(|err| err.to_string())
+            }
+            SafeAction::AnalogValue {
+                unit_id,
+                channel,
+                value,
+            } => AnalogOutput::new(unit_id, self.context.clone())
+                .write_output_channel_value(channel, value)
+                .await
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{
+    analog_out::{AnalogOutput, AnalogOutputError},
+    common::Channel,
+};
+use std::f64::consts::TAU;
+use std::io::BufRead;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+
+#[derive(Error, Debug)]
+pub enum WaveformError {
+    #[error("Invalid Waveform: `{0}`")]
+    InvalidWaveform(String),
+    #[error("CSV Error on line {0}: `{1}`")]
+    Csv(usize, String),
+    #[error("IO Error: `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("Analog Output Error: `{0}`")]
+    AnalogOutput(#[from] AnalogOutputError),
+    #[error("Update period must be greater than zero")]
+    InvalidUpdatePeriod,
+    #[error("Playback task failed")]
+    TaskFailed,
+}
+
+/// Output profiles in raw output units.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Waveform {
+    Square {
+        low: u16,
+        high: u16,
+        period: Duration,
+        // Fraction of the period spent high
+        duty: f64,
+    },
+    Triangle {
+        low: u16,
+        high: u16,
+        period: Duration,
+    },
+    Sine {
+        offset: f64,
+        amplitude: f64,
+        period: Duration,
+    },
+    // Each value is held for its duration
+    Steps(Vec<(Duration, u16)>),
+    // Values at time offsets, interpolated linearly between points
+    Points(Vec<(Duration, u16)>),
+}
+
+impl Waveform {
+    // Periodic waveforms run until stopped
+    pub fn duration(&self) -> Option<Duration> {
+        match self {
+            Waveform::Square { .. } | Waveform::Triangle { .. } | Waveform::Sine { .. } => None,
+            Waveform::Steps(steps) => Some(steps.iter().map(|(duration, _)| *duration).sum()),
+            Waveform::Points(points) => points.last().map(|(at, _)| *at),
+        }
+    }
+
+    pub fn value_at(&self, t: Duration) -> u16 {
+        let phase = |period: &Duration| {
+            if period.is_zero() {
+                0.0
+            } else {
+                (t.as_secs_f64() / period.as_secs_f64()).fract()
+            }
+        };
+        match self {
+            Waveform::Square {
+                low,
+                high,
+                period,
+                duty,
+            } => {
+                if phase(period) < *duty {
+                    *high
+                } else {
+                    *low
+                }
+            }
+            Waveform::Triangle { low, high, period } => {
+                let phase = phase(period);
+                let fraction = if phase < 0.5 {
+                    phase * 2.0
+                } else {
+                    2.0 - phase * 2.0
+                };
+                (*low as f64 + (*high as f64 - *low as f64) * fraction).round() as u16
+            }
+            Waveform::Sine {
+                offset,
+                amplitude,
+                period,
+            } => {
+                let value = offset + amplitude * (phase(period) * TAU).sin();
+                value.round().clamp(0.0, u16::MAX as f64) as u16
+            }
+            Waveform::Steps(steps) => {
+                let mut end = Duration::ZERO;
+                for (duration, value) in steps {
+                    end += *duration;
+                    if t < end {
+                        return *value;
+                    }
+                }
+                steps.last().map(|(_, value)| *value).unwrap_or_default()
+            }
+            Waveform::Points(points) => {
+                let Some(index) = points.iter().position(|(at, _)| t < *at) else {
+                    return points.last().map(|(_, value)| *value).unwrap_or_default();
+                };
+                if index == 0 {
+                    return points[0].1;
+                }
+                let (t0, v0) = points[index - 1];
+                let (t1, v1) = points[index];
+                let fraction = (t - t0).as_secs_f64() / (t1 - t0).as_secs_f64();
+                (v0 as f64 + (v1 as f64 - v0 as f64) * fraction).round() as u16
+            }
+        }
+    }
+
+    /// Reads `seconds,value` rows into a point list, skipping a header row.
+    pub fn from_csv(reader: impl BufRead) -> Result<Self, WaveformError> {
+        let mut points: Vec<(Duration, u16)> = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| WaveformError::Csv(index + 1, reason.to_string());
+            let (time, value) = line
+                .split_once(',')
+                .ok_or_else(|| invalid("expected `seconds,value`"))?;
+            let (Ok(time), Ok(value)) = (time.trim().parse::<f64>(), value.trim().parse::<u16>())
+            else {
+                if index == 0 {
+                    continue;
+                }
+                return Err(invalid("invalid number"));
+            };
+            let time =
+                Duration::try_from_secs_f64(time).map_err(|_| invalid("invalid time offset"))?;
+            if points.last().is_some_and(|(last, _)| time <= *last) {
+                return Err(invalid("time offsets must increase"));
+            }
+            points.push((time, value));
+        }
+        if points.is_empty() {
+            return Err(WaveformError::InvalidWaveform(
+                "no points in CSV".to_string(),
+            ));
+        }
+        Ok(Waveform::Points(points))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlaybackConfig {
+    pub channels: Vec<Channel>,
+    pub update_period: Duration,
+    // Written to every channel when playback ends, fails or is stopped
+    pub safe_value: u16,
+    // Limits periodic waveforms, finite waveforms stop at their end
+    pub duration: Option<Duration>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlaybackOutcome {
+    Completed,
+    Stopped,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PlaybackReport {
+    pub outcome: PlaybackOutcome,
+    pub updates: u64,
+    pub elapsed: Duration,
+    pub requested_rate: f64,
+    pub achieved_rate: f64,
+    pub max_write_latency: Duration,
+}
+
+#[derive(Debug)]
+pub struct PlaybackHandle {
+    stop: Option<oneshot::Sender<()>>,
+    task: JoinHandle<Result<PlaybackReport, WaveformError>>,
+}
+
+impl PlaybackHandle {
+    pub fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+
+    pub async fn finished(self) -> Result<PlaybackReport, WaveformError> {
+        // Keep the stop sender alive, dropping it would stop playback
+        let PlaybackHandle { stop: _stop, task } = self;
+        task.await.map_err(|_| WaveformError::TaskFailed)?
+    }
+}
+
+/// Plays `waveform` on a spawned task. Dropping the handle stops playback.
+pub fn play(
+    output: AnalogOutput,
+    waveform: Waveform,
+    config: PlaybackConfig,
+) -> Result<PlaybackHandle, WaveformError> {
+    if config.update_period.is_zero() {
+        return Err(WaveformError::InvalidUpdatePeriod);
+    }
+    let (stop, stopped) = oneshot::channel();
+    let task = tokio::spawn(run(output, waveform, config, stopped));
+    Ok(PlaybackHandle {
+        stop: Some(stop),
+        task,
+    })
+}
+
+async fn run(
+    mut output: AnalogOutput,
+    waveform: Waveform,
+    config: PlaybackConfig,
+    mut stopped: oneshot::Receiver<()>,
+) -> Result<PlaybackReport, WaveformError> {
+    let end = match (waveform.duration(), config.duration) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    let mut interval = tokio::time::interval(config.update_period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let started = Instant::now();
+    let mut updates = 0u64;
+    let mut max_write_latency = Duration::ZERO;
+
+    let outcome = loop {
+        tokio::select! {
+            _ = &mut stopped => break Ok(PlaybackOutcome::Stopped),
+            _ = interval.tick() => {}
+        }
+        let t = started.elapsed();
+        if end.is_some_and(|end| t >= end) {
+            break Ok(PlaybackOutcome::Completed);
+        }
+        let value = waveform.value_at(t);
+        let write_started = Instant::now();
+        if let Err(err) = write_all(&mut output, &config.channels, value).await {
+            break Err(err);
+        }
+        max_write_latency = max_write_latency.max(write_started.elapsed());
+        updates += 1;
+    };
+
+    // Always try to leave the outputs at the safe value
+    let safe = write_all(&mut output, &config.channels, config.safe_value).await;
+    let outcome = outcome?;
+    safe?;
+
+    let elapsed = started.elapsed();
+    Ok(PlaybackReport {
+        outcome,
+        updates,
+        elapsed,
+        requested_rate: 1.0 / config.update_period.as_secs_f64(),
+        achieved_rate: if elapsed.is_zero() {
+            0.0
+        } else {
+            updates as f64 / elapsed.as_secs_f64()
+        },
+        max_write_latency,
+    })
+}
+
+async fn write_all(
+    output: &mut AnalogOutput,
+    channels: &[Channel],
+    value: u16,
+) -> Result<(), WaveformError> {
+    let mut channels: Vec<u16> = channels.iter().map(|channel| *channel as u16).collect();
+    channels.sort_unstable();
+    channels.dedup();
+    // Neighbouring channels share one write
+    let mut start = 0;
+    while start < channels.len() {
+        let mut end = start + 1;
+        while end < channels.len() && channels[end] == channels[end - 1] + 1 {
+            end += 1;
+        }
+        if let Ok(channel) = Channel::try_from(channels[start] as u8) {
+            output
+                .write_output_channel_values(channel, &vec![value; end - start])
+                .await?;
+        }
+        start = end;
+    }
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use std::pin::pin;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+use waveshare::alarm::{AlarmConfig, AlarmEngine, AlarmKind, AlarmTransition};
+use waveshare::analog_in::ControlMode;
+use waveshare::common::Channel;
+
+const HIGH: AlarmConfig = AlarmConfig {
+    high_high: None,
+    high: Some(8000.0),
+    low: None,
+    low_low: None,
+    deadband: 0.0,
+    hysteresis: 0.0,
+    delay_on: Duration::ZERO,
+    delay_off: Duration::ZERO,
+};
+
+fn engine(mode: ControlMode, config: AlarmConfig) -> AlarmEngine {
+    let mut engine = AlarmEngine::new();
+    engine.configure(Channel::Channel1, mode, config);
+    engine
+}
+
+// The alarm kinds and transitions for a reading on channel 1
+fn update(engine: &mut AlarmEngine, value: u16, now: Instant) -> Vec<(AlarmKind, AlarmTransition)> {
+    engine
+        .update(&[0, value], now)
+        .into_iter()
+        .map(|event| {
+            assert_eq!(event.channel, Channel::Channel1);
+            (event.kind, event.transition)
+        })
+        .collect()
+}
+
+#[test]
+fn limits() {
+    let mut engine = engine(
+        ControlMode::V0V10,
+        AlarmConfig {
+            high_high: Some(9000.0),
+            high: Some(8000.0),
+            low: Some(2000.0),
+            low_low: Some(1000.0),
+            ..HIGH
+        },
+    );
+    let now = Instant::now();
+    assert!(update(&mut engine, 5000, now).is_empty());
+    assert_eq!(
+        update(&mut engine, 8500, now),
+        [(AlarmKind::High, AlarmTransition::Raised)]
+    );
+    assert_eq!(
+        update(&mut engine, 9500, now),
+        [(AlarmKind::HighHigh, AlarmTransition::Raised)]
+    );
+    assert_eq!(
+        update(&mut engine, 500, now),
+        [
+            (AlarmKind::HighHigh, AlarmTransition::Cleared),
+            (AlarmKind::High, AlarmTransition::Cleared),
+            (AlarmKind::Low, AlarmTransition::Raised),
+            (AlarmKind::LowLow, AlarmTransition::Raised),
+        ]
+    );
+    assert!(
+        engine
+            .state(Channel::Channel1, AlarmKind::LowLow)
+            .unwrap()
+            .active
+    );
+    assert!(
+        !engine
+            .state(Channel::Channel1, AlarmKind::High)
+            .unwrap()
+            .active
+    );
+    // Unconfigured channels are not evaluated
+    assert!(engine.state(Channel::Channel0, AlarmKind::High).is_none());
+}
+
+#[test]
+fn hysteresis() {
+    let mut engine = engine(
+        ControlMode::V0V10,
+        AlarmConfig {
+            hysteresis: 100.0,
+            ..HIGH
+        },
+    );
+    let now = Instant::now();
+    assert_eq!(
+        update(&mut engine, 8001, now),
+        [(AlarmKind::High, AlarmTransition::Raised)]
+    );
+    assert!(update(&mut engine, 7950, now).is_empty());
+    assert!(update(&mut engine, 7900, now).is_empty());
+    assert_eq!(
+        update(&mut engine, 7899, now),
+        [(AlarmKind::High, AlarmTransition::Cleared)]
+    );
+}
+
+#[test]
+fn deadband() {
+    let mut engine = engine(
+        ControlMode::V0V10,
+        AlarmConfig {
+            deadband: 50.0,
+            ..HIGH
+        },
+    );
+    let now = Instant::now();
+    assert!(update(&mut engine, 7980, now).is_empty());
+    // Within the deadband of the last value, so still 7980
+    assert!(update(&mut engine, 8020, now).is_empty());
+    let events = engine.update(&[0, 8040], now);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].transition, AlarmTransition::Raised);
+    assert_eq!(events[0].value, 8040.0);
+}
+
+#[test]
+fn delay_on_and_off() {
+    let mut engine = engine(
+        ControlMode::V0V10,
+        AlarmConfig {
+            delay_on: Duration::from_secs(1),
+            delay_off: Duration::from_secs(2),
+            ..HIGH
+        },
+    );
+    let start = Instant::now();
+    let at = |millis| start + Duration::from_millis(millis);
+    assert!(update(&mut engine, 9000, at(0)).is_empty());
+    // Dropping back below the limit starts the delay over
+    assert!(update(&mut engine, 7000, at(500)).is_empty());
+    assert!(update(&mut engine, 9000, at(1200)).is_empty());
+    assert!(update(&mut engine, 9000, at(2100)).is_empty());
+    let events = engine.update(&[0, 9000], at(2200));
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].transition, AlarmTransition::Raised);
+    assert_eq!(events[0].timestamp, at(2200));
+
+    assert!(update(&mut engine, 7000, at(3000)).is_empty());
+    assert!(update(&mut engine, 7000, at(4900)).is_empty());
+    assert_eq!(
+        update(&mut engine, 7000, at(5000)),
+        [(AlarmKind::High, AlarmTransition::Cleared)]
+    );
+}
+
+#[tokio::test]
+async fn acknowledge() {
+    let mut engine = engine(ControlMode::V0V10, HIGH);
+    let mut events = pin!(engine.events());
+    assert!(!engine.acknowledge(Channel::Channel1, AlarmKind::High));
+
+    update(&mut engine, 9000, Instant::now());
+    assert!(engine.acknowledge(Channel::Channel1, AlarmKind::High));
+    assert!(!engine.acknowledge(Channel::Channel1, AlarmKind::High));
+    let state = engine.state(Channel::Channel1, AlarmKind::High).unwrap();
+    assert!(state.active && state.acknowledged);
+
+    let raised = events.next().await.unwrap();
+    assert_eq!(raised.transition, AlarmTransition::Raised);
+    let acknowledged = events.next().await.unwrap();
+    assert_eq!(
+        (
+            acknowledged.kind,
+            acknowledged.transition,
+            acknowledged.value
+        ),
+        (AlarmKind::High, AlarmTransition::Acknowledged, 9000.0)
+    );
+
+    // A new occurrence needs acknowledging again
+    update(&mut engine, 7000, Instant::now());
+    update(&mut engine, 9000, Instant::now());
+    let state = engine.state(Channel::Channel1, AlarmKind::High).unwrap();
+    assert!(state.active && !state.acknowledged);
+}
+
+#[test]
+fn open_loop_detection() {
+    let config = AlarmConfig {
+        high: None,
+        hysteresis: 50.0,
+        ..HIGH
+    };
+    let mut engine = engine(ControlMode::C4C20, config);
+    let now = Instant::now();
+    assert!(update(&mut engine, 4000, now).is_empty());
+    assert_eq!(
+        update(&mut engine, 3000, now),
+        [(AlarmKind::OpenLoop, AlarmTransition::Raised)]
+    );
+    assert!(update(&mut engine, 3820, now).is_empty());
+    assert_eq!(
+        update(&mut engine, 3900, now),
+        [(AlarmKind::OpenLoop, AlarmTransition::Cleared)]
+    );
+
+    // Only 4~20mA inputs can tell a broken loop from a low reading
+    let mut engine = self::engine(ControlMode::C0C20, config);
+    assert!(update(&mut engine, 0, now).is_empty());
+    assert!(engine
+        .state(Channel::Channel1, AlarmKind::OpenLoop)
+        .is_none());
+}
@@ -0,0 +1,42 @@
+mod simulator;
+
+use simulator::Simulator;
+use waveshare::analog_in::{AnalogInput, AnalogInputError};
+use waveshare::analog_out::{AnalogOutput, AnalogOutputError};
+use waveshare::common::Channel;
+
+#[tokio::test]
+async fn channels_beyond_the_module_are_rejected() {
+    let (simulator, context) = Simulator::connect(1);
+    let mut output = AnalogOutput::new(1, context.clone());
+    assert!(matches!(
+        output
+            .write_output_channel_value(Channel::Channel8, 1000)
+            .await,
+        Err(AnalogOutputError::InvalidChannel(Channel::Channel8))
+    ));
+    assert!(matches!(
+        output
+            .write_output_channel_values(Channel::Channel6, &[1, 2, 3])
+            .await,
+        Err(AnalogOutputError::InvalidChannel(Channel::Channel6))
+    ));
+    let mut input = AnalogInput::new(1, context);
+    assert!(matches!(
+        input.read_input_channel_status(Channel::Channel31).await,
+        Err(AnalogInputError::InvalidChannel(Channel::Channel31))
+    ));
+    assert!(simulator.state.lock().unwrap().requests.is_empty());
+
+    output
+        .write_output_channel_values(Channel::Channel5, &[1, 2, 3])
+        .await
+        .unwrap();
+    assert_eq!(
+        output
+            .read_output_channel_value(Channel::Channel7)
+            .await
+            .unwrap(),
+        3
+    );
+}
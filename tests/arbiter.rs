@@ -0,0 +1,103 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use waveshare::arbiter::Priority;
+use waveshare::ThreadSafeContext;
+
+type Order = Arc<Mutex<Vec<Priority>>>;
+
+// Waits for the bus at `priority` and records when it got it
+fn request(context: &ThreadSafeContext, priority: Priority, order: &Order) -> JoinHandle<()> {
+    let context = context.clone().with_priority(priority);
+    let order = Arc::clone(order);
+    tokio::spawn(async move {
+        let _bus = context.lock().await;
+        order.lock().unwrap().push(priority);
+    })
+}
+
+async fn wait_queued(context: &ThreadSafeContext, queued: usize) {
+    while context.metrics().queued() < queued {
+        tokio::task::yield_now().await;
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn waiters_are_granted_highest_priority_first() {
+    let (_simulator, context) = Simulator::connect(1);
+    let order = Order::default();
+    let bus = context.lock().await;
+
+    let mut tasks = Vec::new();
+    for (queued, priority) in Priority::ALL.into_iter().rev().enumerate() {
+        tasks.push(request(&context, priority, &order));
+        wait_queued(&context, queued + 1).await;
+    }
+    drop(bus);
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    assert_eq!(*order.lock().unwrap(), Priority::ALL);
+    let metrics = context.metrics();
+    assert_eq!(metrics.queued(), 0);
+    // The holder was a `Control` handle as well
+    assert_eq!(metrics.level(Priority::Control).granted, 2);
+    for priority in Priority::ALL {
+        assert_eq!(metrics.level(priority).peak_queued, 1);
+        assert_eq!(metrics.level(priority).promoted, 0);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn starved_waiters_are_promoted() {
+    let (_simulator, context) = Simulator::connect(1);
+    let context = context.with_starvation_limit(Duration::from_millis(100));
+    let order = Order::default();
+    let bus = context.lock().await;
+
+    let diagnostics = request(&context, Priority::Diagnostics, &order);
+    wait_queued(&context, 1).await;
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let control = request(&context, Priority::Control, &order);
+    wait_queued(&context, 2).await;
+    drop(bus);
+    diagnostics.await.unwrap();
+    control.await.unwrap();
+
+    assert_eq!(
+        *order.lock().unwrap(),
+        [Priority::Diagnostics, Priority::Control]
+    );
+    let metrics = context.metrics();
+    assert_eq!(metrics.level(Priority::Diagnostics).promoted, 1);
+    assert!(metrics.level(Priority::Diagnostics).longest_wait >= Duration::from_millis(150));
+    assert_eq!(metrics.level(Priority::Control).promoted, 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn starved_waiters_never_go_ahead_of_safety() {
+    let (_simulator, context) = Simulator::connect(1);
+    let context = context.with_starvation_limit(Duration::from_millis(100));
+    let order = Order::default();
+    let bus = context.lock().await;
+
+    let diagnostics = request(&context, Priority::Diagnostics, &order);
+    wait_queued(&context, 1).await;
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let safety = request(&context, Priority::Safety, &order);
+    wait_queued(&context, 2).await;
+    drop(bus);
+    diagnostics.await.unwrap();
+    safety.await.unwrap();
+
+    assert_eq!(
+        *order.lock().unwrap(),
+        [Priority::Safety, Priority::Diagnostics]
+    );
+    // Diagnostics was the highest left by the time it went
+    assert_eq!(context.metrics().level(Priority::Diagnostics).promoted, 0);
+}
@@ -0,0 +1,134 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_modbus::{Request, Slave};
+use waveshare::broadcast::{Broadcast, BroadcastError};
+use waveshare::common::Channel;
+use waveshare::digital::{Action, DigitalIO, IoBank};
+
+fn coil_writes(simulator: &Simulator, address: u16) -> usize {
+    simulator
+        .state
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .filter(|request| matches!(request, Request::WriteSingleCoil(addr, _) if *addr == address))
+        .count()
+}
+
+#[tokio::test(start_paused = true)]
+async fn writes_reach_every_unit() {
+    let (simulators, context) = Simulator::connect_bus(&[1, 2]);
+    let mut broadcast = Broadcast::new(context.clone());
+    broadcast.open_all_outputs().await.unwrap();
+    for simulator in &simulators {
+        assert_eq!(simulator.state.lock().unwrap().coils, [true; 8]);
+    }
+
+    // Unit 2 is still addressed on its own afterwards
+    let mut io: DigitalIO = DigitalIO::new(2, context);
+    assert_eq!(io.read_output_channels().await.unwrap(), [true; 8]);
+    assert!(matches!(
+        simulators[1].state.lock().unwrap().requests.last(),
+        Some(Request::ReadCoils(..))
+    ));
+    assert!(!matches!(
+        simulators[0].state.lock().unwrap().requests.last(),
+        Some(Request::ReadCoils(..))
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn drivers_are_not_redirected_by_broadcasts() {
+    let (simulators, context) = Simulator::connect_bus(&[1, 2]);
+    let mut broadcast = Broadcast::new(context.clone());
+    let mut io: DigitalIO = DigitalIO::new(1, context);
+
+    let broadcasts = async {
+        for _ in 0..5 {
+            broadcast
+                .write_output_channel(Channel::Channel3, Action::On)
+                .await
+                .unwrap();
+        }
+    };
+    let writes = async {
+        for _ in 0..5 {
+            io.write_output_channel(Channel::Channel0, Action::On)
+                .await
+                .unwrap();
+        }
+    };
+    tokio::join!(broadcasts, writes);
+
+    assert_eq!(coil_writes(&simulators[0], 0), 5);
+    assert_eq!(coil_writes(&simulators[1], 0), 0);
+    assert_eq!(coil_writes(&simulators[1], 3), 5);
+}
+
+#[tokio::test(start_paused = true)]
+async fn the_selected_slave_is_kept() {
+    let (simulators, mut context) = Simulator::connect_bus(&[1, 2]);
+    context.set_slave(Slave(2)).await;
+    Broadcast::new(context.clone())
+        .close_all_outputs()
+        .await
+        .unwrap();
+    context.read_coils(0, 8).await.unwrap().unwrap();
+    assert!(matches!(
+        simulators[1].state.lock().unwrap().requests.last(),
+        Some(Request::ReadCoils(..))
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn frames_are_written_whole_without_pacing() {
+    let (simulators, context) = Simulator::connect_bus(&[1, 2]);
+    context.set_silent_interval(Duration::ZERO).await;
+    let mut broadcast = Broadcast::new(context).with_turnaround(Duration::ZERO);
+    broadcast
+        .write_output_bank(IoBank::from(0b1010_0101))
+        .await
+        .unwrap();
+    for simulator in &simulators {
+        assert_eq!(
+            simulator.state.lock().unwrap().coils,
+            <[bool; 8]>::from(IoBank::from(0b1010_0101))
+        );
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn bus_is_held_for_the_turnaround() {
+    let (_simulators, context) = Simulator::connect_bus(&[1, 2]);
+    let mut broadcast = Broadcast::new(context).with_turnaround(Duration::from_millis(250));
+    let started = Instant::now();
+    broadcast.close_all_outputs().await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(250));
+}
+
+#[tokio::test(start_paused = true)]
+async fn analog_channels_beyond_the_module_are_rejected() {
+    let (simulator, context) = Simulator::connect(1);
+    let mut broadcast = Broadcast::new(context);
+    assert!(matches!(
+        broadcast.write_analog_output(Channel::Channel8, 1000).await,
+        Err(BroadcastError::InvalidChannel(Channel::Channel8))
+    ));
+    assert!(matches!(
+        broadcast
+            .write_analog_outputs(Channel::Channel6, &[1, 2, 3])
+            .await,
+        Err(BroadcastError::InvalidChannel(Channel::Channel6))
+    ));
+    assert!(simulator.state.lock().unwrap().requests.is_empty());
+
+    broadcast
+        .write_analog_outputs(Channel::Channel5, &[1, 2, 3])
+        .await
+        .unwrap();
+    assert_eq!(simulator.state.lock().unwrap().requests.len(), 1);
+}
@@ -0,0 +1,73 @@
+use waveshare::calibration::{Calibration, CalibrationSet};
+use waveshare::common::Channel;
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 1e-9,
+        "{} != {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn tables_need_two_monotonic_points() {
+    assert!(Calibration::table(vec![]).is_err());
+    assert!(Calibration::table(vec![(0.0, 0.0)]).is_err());
+    assert!(Calibration::table(vec![(0.0, 0.0), (1.0, 1.0), (2.0, 0.5)]).is_err());
+    assert!("table 0:0".parse::<Calibration>().is_err());
+    assert!(Calibration::table(vec![(0.0, 0.0), (1.0, 1.0)]).is_ok());
+}
+
+#[test]
+fn table_interpolates_between_points() {
+    // Given out of order, sorted on construction
+    let table = Calibration::table(vec![(1000.0, 10.0), (0.0, 0.0), (4000.0, 100.0)]).unwrap();
+    assert_close(table.apply(0.0), 0.0);
+    assert_close(table.apply(500.0), 5.0);
+    assert_close(table.apply(1000.0), 10.0);
+    assert_close(table.apply(2500.0), 55.0);
+    assert_close(table.apply(4000.0), 100.0);
+}
+
+#[test]
+fn table_extrapolates_from_end_segments() {
+    let table = Calibration::table(vec![(0.0, 0.0), (1000.0, 10.0), (4000.0, 100.0)]).unwrap();
+    assert_close(table.apply(-1000.0), -10.0);
+    assert_close(table.apply(5000.0), 130.0);
+}
+
+#[test]
+fn table_inverts() {
+    for points in [
+        vec![(0.0, 0.0), (1000.0, 10.0), (4000.0, 100.0)],
+        vec![(0.0, 100.0), (1000.0, 90.0), (4000.0, 0.0)],
+    ] {
+        let table = Calibration::table(points).unwrap();
+        for raw in [0.0, 250.0, 1000.0, 3000.0, 4000.0] {
+            assert_close(table.invert(table.apply(raw)).unwrap(), raw);
+        }
+    }
+}
+
+#[test]
+fn linear_inverts() {
+    let linear = Calibration::linear(-4.0, 0.004);
+    assert_close(linear.apply(1000.0), 0.0);
+    assert_close(linear.invert(0.0).unwrap(), 1000.0);
+    assert!(Calibration::linear(1.0, 0.0).invert(1.0).is_err());
+}
+
+#[test]
+fn set_round_trips_through_text() {
+    let mut set = CalibrationSet::new();
+    set.set(Channel::Channel0, Calibration::linear(1.5, 2.0));
+    set.set(
+        Channel::Channel3,
+        Calibration::table(vec![(0.0, 0.0), (10.0, 5.0), (20.0, 20.0)]).unwrap(),
+    );
+    let parsed: CalibrationSet = set.to_string().parse().unwrap();
+    assert_eq!(parsed, set);
+    assert_close(parsed.apply(Channel::Channel3, 15), 12.5);
+    assert_close(parsed.apply(Channel::Channel1, 7), 7.0);
+}
@@ -0,0 +1,135 @@
+use waveshare::analog_in::{self, AnalogInputError};
+use waveshare::analog_out::{self, AnalogOutputError};
+use waveshare::common::{Baudrates, Channel, Parity};
+use waveshare::digital::{self, DigitalIOError};
+
+#[test]
+fn baudrates_round_trip() {
+    for baudrate in Baudrates::ALL {
+        assert_eq!(Baudrates::try_from(baudrate.bps()), Ok(baudrate));
+        assert_eq!(baudrate.to_string(), baudrate.bps().to_string());
+        assert_eq!(baudrate.to_string().parse(), Ok(baudrate));
+        // The register code is the enum value
+        assert_eq!(Baudrates::try_from(baudrate as u16), Ok(baudrate));
+    }
+    assert_eq!(Baudrates::B115200.bps(), 115200);
+    assert_eq!(" 9600 ".parse(), Ok(Baudrates::B9600));
+}
+
+#[test]
+fn unsupported_baudrates_are_rejected() {
+    for bps in [0u32, 1200, 2400, 14400, 230400, 4_800_000] {
+        assert!(Baudrates::try_from(bps).is_err());
+        assert!(bps.to_string().parse::<Baudrates>().is_err());
+    }
+    for text in ["", "fast", "9600bps", "-9600"] {
+        assert!(text.parse::<Baudrates>().is_err());
+    }
+    assert!(Baudrates::try_from(0x08u16).is_err());
+}
+
+#[test]
+fn parity_round_trip() {
+    for parity in [Parity::None, Parity::Even, Parity::Odd] {
+        assert_eq!(parity.to_string().parse(), Ok(parity));
+        assert_eq!(Parity::try_from(parity as u16), Ok(parity));
+    }
+    assert_eq!("E".parse(), Ok(Parity::Even));
+    assert_eq!(" Odd ".parse(), Ok(Parity::Odd));
+    for text in ["", "mark", "space", "2"] {
+        assert!(text.parse::<Parity>().is_err());
+    }
+    assert!(Parity::try_from(0x03).is_err());
+}
+
+#[test]
+fn channel_round_trip() {
+    for (index, channel) in Channel::ALL.into_iter().enumerate() {
+        assert_eq!(u8::from(channel), index as u8);
+        assert_eq!(Channel::try_from(index as u8), Ok(channel));
+        assert_eq!(channel.to_string(), index.to_string());
+        assert_eq!(channel.to_string().parse(), Ok(channel));
+    }
+    for text in ["32", "255", "256", "-1", "", "Channel3"] {
+        assert!(text.parse::<Channel>().is_err());
+    }
+    assert!(Channel::try_from(32).is_err());
+}
+
+#[test]
+fn analog_input_modes_round_trip() {
+    for value in 0..=4 {
+        let mode = analog_in::ControlMode::from_u16(value).unwrap();
+        assert_eq!(mode as u16, value);
+        assert_eq!(
+            mode.to_string().parse::<analog_in::ControlMode>().unwrap(),
+            mode
+        );
+    }
+    assert_eq!(
+        "4-20ma".parse::<analog_in::ControlMode>().unwrap(),
+        analog_in::ControlMode::C4C20
+    );
+    assert!(matches!(
+        analog_in::ControlMode::from_u16(5),
+        Err(AnalogInputError::InvalidControlMode)
+    ));
+    for text in ["", "4-20", "0-5V", "1200", "32"] {
+        assert!(matches!(
+            text.parse::<analog_in::ControlMode>(),
+            Err(AnalogInputError::InvalidControlMode)
+        ));
+    }
+}
+
+#[test]
+fn analog_output_modes_round_trip() {
+    for value in 0..=4 {
+        let mode = analog_out::ControlMode::from_u16(value).unwrap();
+        assert_eq!(mode as u16, value);
+        assert_eq!(
+            mode.to_string().parse::<analog_out::ControlMode>().unwrap(),
+            mode
+        );
+    }
+    assert_eq!(
+        " RAW ".parse::<analog_out::ControlMode>().unwrap(),
+        analog_out::ControlMode::RAW
+    );
+    assert!(matches!(
+        analog_out::ControlMode::from_u16(5),
+        Err(AnalogOutputError::InvalidControlMode)
+    ));
+    for text in ["", "4-20", "0-5V", "1200", "32"] {
+        assert!(matches!(
+            text.parse::<analog_out::ControlMode>(),
+            Err(AnalogOutputError::InvalidControlMode)
+        ));
+    }
+}
+
+#[test]
+fn digital_modes_round_trip() {
+    for value in 0..=2 {
+        let mode = digital::ControlMode::from_u16(value).unwrap();
+        assert_eq!(mode as u16, value);
+        assert_eq!(
+            mode.to_string().parse::<digital::ControlMode>().unwrap(),
+            mode
+        );
+    }
+    assert_eq!(
+        "Linked".parse::<digital::ControlMode>().unwrap(),
+        digital::ControlMode::Linked
+    );
+    assert!(matches!(
+        digital::ControlMode::from_u16(3),
+        Err(DigitalIOError::InvalidControlMode)
+    ));
+    for text in ["", "toggle", "1", "1200", "32"] {
+        assert!(matches!(
+            text.parse::<digital::ControlMode>(),
+            Err(DigitalIOError::InvalidControlMode)
+        ));
+    }
+}
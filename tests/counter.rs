@@ -0,0 +1,89 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio::time::Instant;
+use waveshare::common::Channel;
+use waveshare::counter::{self, CounterConfig, CounterError, PulseCounter, Totals};
+use waveshare::digital::DigitalIO;
+use waveshare::edges::Edge;
+
+const GAP: Duration = Duration::from_millis(10);
+
+// Samples a square wave on channel 0 that is high for `width` samples, then
+// low for `width` samples
+fn square_wave(width: u32, samples: u32) -> PulseCounter {
+    let mut counter = PulseCounter::new(Edge::Rising, Duration::ZERO, Duration::from_secs(10));
+    let start = Instant::now();
+    for sample in 0..samples {
+        let high = (sample / width) % 2 == 1;
+        counter.update(&[high, false], start + GAP * sample);
+    }
+    counter
+}
+
+#[test]
+fn counts_rising_edges() {
+    let count = square_wave(5, 100).count(Channel::Channel0);
+    assert_eq!(count.total, 10);
+    assert!((count.frequency - 10.0).abs() < 1e-6);
+    assert!(!count.undersampled);
+}
+
+#[test]
+fn pulses_at_the_sampling_limit_are_flagged() {
+    // One sample high, one low, the fastest pulse that can still be seen
+    let count = square_wave(1, 100).count(Channel::Channel0);
+    assert_eq!(count.total, 50);
+    assert!(count.undersampled);
+}
+
+#[test]
+fn pulses_well_below_the_limit_are_not_flagged() {
+    for width in [3, 4, 10] {
+        assert!(
+            !square_wave(width, 200)
+                .count(Channel::Channel0)
+                .undersampled
+        );
+    }
+}
+
+#[test]
+fn slowing_down_clears_the_flag() {
+    let mut counter = PulseCounter::new(Edge::Rising, Duration::ZERO, Duration::from_millis(200));
+    let start = Instant::now();
+    for sample in 0..40 {
+        counter.update(&[sample % 2 == 1], start + GAP * sample);
+    }
+    assert!(counter.count(Channel::Channel0).undersampled);
+    for sample in 40..200 {
+        counter.update(&[(sample / 10) % 2 == 1], start + GAP * sample);
+    }
+    assert!(!counter.count(Channel::Channel0).undersampled);
+}
+
+#[test]
+fn totals_round_trip_through_text() {
+    let counter = square_wave(2, 40);
+    let totals = counter.totals();
+    assert_eq!(totals.to_string().parse::<Totals>().unwrap(), totals);
+    let restored = PulseCounter::new(Edge::Rising, Duration::ZERO, Duration::from_secs(10))
+        .with_totals(&totals);
+    assert_eq!(restored.count(Channel::Channel0).total, 10);
+}
+
+#[tokio::test]
+async fn zero_poll_period_is_rejected() {
+    let (simulator, context) = Simulator::connect(1);
+    let io: DigitalIO = DigitalIO::new(1, context);
+    let config = CounterConfig {
+        poll_period: Duration::ZERO,
+        ..CounterConfig::default()
+    };
+    assert!(matches!(
+        counter::spawn(io, config),
+        Err(CounterError::InvalidPollPeriod)
+    ));
+    assert!(simulator.state.lock().unwrap().requests.is_empty());
+}
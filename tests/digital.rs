@@ -0,0 +1,74 @@
+mod simulator;
+
+use simulator::Simulator;
+use waveshare::common::Channel;
+use waveshare::digital::{DigitalIO, IoBank};
+
+fn bank(channels: &[Channel]) -> IoBank {
+    let mut bank = IoBank::default();
+    for channel in channels {
+        bank[*channel] = true;
+    }
+    bank
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn concurrent_masked_updates_keep_every_bit() {
+    let (simulator, context) = Simulator::connect(1);
+    let initial = IoBank::from(0xF0);
+    simulator.state.lock().unwrap().coils = initial.into();
+
+    // One task sets the low channels while the other clears the high ones
+    let mut tasks = Vec::new();
+    for (set, channels) in [
+        (true, &IoBank::<8>::CHANNELS[..4]),
+        (false, &IoBank::<8>::CHANNELS[4..]),
+    ] {
+        let mut io: DigitalIO = DigitalIO::new(1, context.clone());
+        tasks.push(tokio::spawn(async move {
+            let mut results = Vec::new();
+            for channel in channels {
+                let mask = bank(&[*channel]);
+                let (before, after) = if set {
+                    io.update_output_channels(mask, IoBank::default()).await
+                } else {
+                    io.update_output_channels(IoBank::default(), mask).await
+                }
+                .unwrap();
+                assert_eq!(before[*channel], !set);
+                assert_eq!(after, before ^ mask);
+                results.push((before, after));
+            }
+            results
+        }));
+    }
+    let mut results = Vec::new();
+    for task in tasks {
+        results.extend(task.await.unwrap());
+    }
+
+    assert_eq!(
+        simulator.state.lock().unwrap().coils,
+        <[bool; 8]>::from(IoBank::from(0x0F))
+    );
+    // Every update saw the one before it, whichever task made it
+    results.sort_by_key(|(before, _)| (*before ^ initial).count());
+    assert_eq!(results[0].0, initial);
+    for pair in results.windows(2) {
+        assert_eq!(pair[0].1, pair[1].0);
+    }
+    assert_eq!(results[7].1, IoBank::from(0x0F));
+}
+
+#[tokio::test]
+async fn unchanged_banks_are_not_written() {
+    let (simulator, context) = Simulator::connect(1);
+    simulator.state.lock().unwrap().coils[2] = true;
+    let mut io: DigitalIO = DigitalIO::new(1, context);
+    let (before, after) = io
+        .update_output_channels(bank(&[Channel::Channel2]), IoBank::default())
+        .await
+        .unwrap();
+    assert_eq!(before, after);
+    assert_eq!(simulator.state.lock().unwrap().requests.len(), 1);
+}
@@ -0,0 +1,140 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::pin::pin;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+use waveshare::common::Channel;
+use waveshare::digital::DigitalIO;
+use waveshare::edges::{
+    edge_stream, Edge, EdgeConfig, EdgeDetector, EdgeError, EdgeEvent, InputEvent,
+};
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+fn edge(channel: Channel, edge: Edge, timestamp: Instant) -> InputEvent {
+    InputEvent::Edge(EdgeEvent {
+        channel,
+        edge,
+        timestamp,
+    })
+}
+
+#[test]
+fn first_sample_is_the_baseline() {
+    let mut detector = EdgeDetector::new(Duration::ZERO, None);
+    let start = Instant::now();
+    assert!(detector.update(&[true, false], start).is_empty());
+    assert_eq!(detector.states(), [true, false]);
+
+    assert_eq!(
+        detector.update(&[false, true], start + ms(10)),
+        [
+            edge(Channel::Channel0, Edge::Falling, start + ms(10)),
+            edge(Channel::Channel1, Edge::Rising, start + ms(10)),
+        ]
+    );
+    assert_eq!(detector.states(), [false, true]);
+    assert!(detector.update(&[false, true], start + ms(20)).is_empty());
+}
+
+#[test]
+fn changes_are_reported_once_they_outlast_the_debounce() {
+    let mut detector = EdgeDetector::new(ms(30), None);
+    let start = Instant::now();
+    detector.update(&[false], start);
+    assert!(detector.update(&[true], start + ms(10)).is_empty());
+    assert!(detector.update(&[true], start + ms(20)).is_empty());
+    // Timestamped from when the change was first seen
+    assert_eq!(
+        detector.update(&[true], start + ms(40)),
+        [edge(Channel::Channel0, Edge::Rising, start + ms(10))]
+    );
+    assert_eq!(detector.states(), [true]);
+}
+
+#[test]
+fn bounces_are_ignored() {
+    let mut detector = EdgeDetector::new(ms(30), None);
+    let start = Instant::now();
+    detector.update(&[false], start);
+    assert!(detector.update(&[true], start + ms(10)).is_empty());
+    assert!(detector.update(&[false], start + ms(20)).is_empty());
+    // The debounce starts over on the next change
+    assert!(detector.update(&[true], start + ms(30)).is_empty());
+    assert!(detector.update(&[true], start + ms(50)).is_empty());
+    assert_eq!(detector.states(), [false]);
+    assert_eq!(
+        detector.update(&[true], start + ms(60)),
+        [edge(Channel::Channel0, Edge::Rising, start + ms(30))]
+    );
+}
+
+#[test]
+fn long_gaps_between_samples_are_reported() {
+    let mut detector = EdgeDetector::new(Duration::ZERO, Some(ms(50)));
+    let start = Instant::now();
+    assert!(detector.update(&[false], start).is_empty());
+    assert!(detector.update(&[false], start + ms(50)).is_empty());
+    assert_eq!(
+        detector.update(&[true], start + ms(130)),
+        [
+            InputEvent::MissedTransitions {
+                gap: ms(80),
+                timestamp: start + ms(130),
+            },
+            edge(Channel::Channel0, Edge::Rising, start + ms(130)),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn stream_yields_input_changes() {
+    let (simulator, context) = Simulator::connect(1);
+    let io: DigitalIO = DigitalIO::new(1, context);
+    let mut events = pin!(edge_stream(io, EdgeConfig::default()).unwrap());
+
+    // Let the first poll take the baseline
+    tokio::time::sleep(ms(50)).await;
+    simulator.state.lock().unwrap().inputs[2] = true;
+    let Some(Ok(InputEvent::Edge(event))) = events.next().await else {
+        panic!("expected an edge");
+    };
+    assert_eq!(
+        (event.channel, event.edge),
+        (Channel::Channel2, Edge::Rising)
+    );
+
+    simulator.state.lock().unwrap().failing = true;
+    assert!(matches!(events.next().await, Some(Err(_))));
+    {
+        let mut state = simulator.state.lock().unwrap();
+        state.failing = false;
+        state.inputs[2] = false;
+    }
+    let Some(Ok(InputEvent::Edge(event))) = events.next().await else {
+        panic!("expected an edge");
+    };
+    assert_eq!(
+        (event.channel, event.edge),
+        (Channel::Channel2, Edge::Falling)
+    );
+}
+
+#[tokio::test]
+async fn zero_poll_period_is_rejected() {
+    let (simulator, context) = Simulator::connect(1);
+    let io: DigitalIO = DigitalIO::new(1, context);
+    let config = EdgeConfig {
+        poll_period: Duration::ZERO,
+        ..EdgeConfig::default()
+    };
+    assert!(matches!(
+        edge_stream(io, config),
+        Err(EdgeError::InvalidPollPeriod)
+    ));
+    assert!(simulator.state.lock().unwrap().requests.is_empty());
+}
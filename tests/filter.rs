@@ -0,0 +1,167 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio::time::Instant;
+use waveshare::analog_in::AnalogInput;
+use waveshare::common::Channel;
+use waveshare::filter::{
+    AnalogSampler, ExponentialSmoothing, Filter, FilterChain, FilterError, FilterSpec, Median,
+    MovingAverage, RateLimit,
+};
+
+fn run(filter: &mut dyn Filter, values: &[f64]) -> Vec<f64> {
+    let now = Instant::now();
+    values
+        .iter()
+        .map(|value| filter.apply(*value, now))
+        .collect()
+}
+
+#[test]
+fn moving_average() {
+    let mut filter = MovingAverage::new(3).unwrap();
+    assert_eq!(
+        run(&mut filter, &[1.0, 2.0, 3.0, 4.0]),
+        [1.0, 1.5, 2.0, 3.0]
+    );
+    filter.reset();
+    assert_eq!(run(&mut filter, &[10.0]), [10.0]);
+}
+
+#[test]
+fn exponential_smoothing() {
+    let mut filter = ExponentialSmoothing::new(0.5).unwrap();
+    assert_eq!(run(&mut filter, &[0.0, 10.0, 10.0]), [0.0, 5.0, 7.5]);
+    filter.reset();
+    assert_eq!(run(&mut filter, &[10.0]), [10.0]);
+
+    let mut filter = ExponentialSmoothing::new(1.0).unwrap();
+    assert_eq!(run(&mut filter, &[0.0, 10.0, 3.0]), [0.0, 10.0, 3.0]);
+}
+
+#[test]
+fn median() {
+    let mut filter = Median::new(3).unwrap();
+    assert_eq!(
+        run(&mut filter, &[5.0, 100.0, 6.0, 7.0]),
+        [5.0, 52.5, 6.0, 7.0]
+    );
+    filter.reset();
+    assert_eq!(run(&mut filter, &[10.0]), [10.0]);
+}
+
+#[test]
+fn rate_limit() {
+    let mut filter = RateLimit::new(10.0).unwrap();
+    let start = Instant::now();
+    assert_eq!(filter.apply(0.0, start), 0.0);
+    assert_eq!(filter.apply(100.0, start + Duration::from_secs(1)), 10.0);
+    assert_eq!(
+        filter.apply(-100.0, start + Duration::from_millis(1500)),
+        5.0
+    );
+    assert_eq!(filter.apply(6.0, start + Duration::from_secs(2)), 6.0);
+    filter.reset();
+    assert_eq!(filter.apply(100.0, start + Duration::from_secs(2)), 100.0);
+}
+
+#[test]
+fn invalid_parameters_are_rejected() {
+    assert_eq!(
+        MovingAverage::new(0).unwrap_err(),
+        FilterError::InvalidWindow
+    );
+    assert_eq!(Median::new(0).unwrap_err(), FilterError::InvalidWindow);
+    for alpha in [0.0, -0.5, 1.5, f64::NAN, f64::INFINITY] {
+        assert_eq!(
+            ExponentialSmoothing::new(alpha).unwrap_err(),
+            FilterError::InvalidAlpha
+        );
+    }
+    for rate in [0.0, -10.0, f64::NAN, f64::INFINITY] {
+        assert_eq!(RateLimit::new(rate).unwrap_err(), FilterError::InvalidRate);
+    }
+}
+
+#[test]
+fn specs_parse() {
+    for (text, spec) in [
+        ("moving_average:4", FilterSpec::MovingAverage(4)),
+        (" ema : 0.2 ", FilterSpec::ExponentialSmoothing(0.2)),
+        ("median:5", FilterSpec::Median(5)),
+        ("rate_limit:2.5", FilterSpec::RateLimit(2.5)),
+    ] {
+        assert_eq!(text.parse::<FilterSpec>(), Ok(spec));
+    }
+}
+
+#[test]
+fn invalid_specs_are_rejected() {
+    for text in [
+        "median",
+        "median:",
+        "median:x",
+        "moving_average:-1",
+        "lowpass:3",
+        "",
+    ] {
+        assert_eq!(
+            text.parse::<FilterSpec>(),
+            Err(FilterError::InvalidSpec(text.to_string()))
+        );
+    }
+    for (text, err) in [
+        ("ema:NaN", FilterError::InvalidAlpha),
+        ("ema:0", FilterError::InvalidAlpha),
+        ("ema:1.5", FilterError::InvalidAlpha),
+        ("median:0", FilterError::InvalidWindow),
+        ("moving_average:0", FilterError::InvalidWindow),
+        ("rate_limit:inf", FilterError::InvalidRate),
+        ("rate_limit:-1", FilterError::InvalidRate),
+    ] {
+        assert_eq!(text.parse::<FilterSpec>(), Err(err));
+    }
+}
+
+#[test]
+fn chains_apply_filters_in_order() {
+    let mut chain =
+        FilterChain::from_specs(&[FilterSpec::Median(3), FilterSpec::MovingAverage(2)]).unwrap();
+    let now = Instant::now();
+    // The spike is removed before it reaches the average
+    let values: Vec<f64> = [10.0, 10.0, 1000.0, 10.0]
+        .into_iter()
+        .map(|value| chain.apply(value, now))
+        .collect();
+    assert_eq!(values, [10.0, 10.0, 10.0, 10.0]);
+
+    assert!(matches!(
+        FilterChain::from_specs(&[FilterSpec::Median(3), FilterSpec::Median(0)]),
+        Err(FilterError::InvalidWindow)
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn sampler_filters_configured_channels() {
+    let (simulator, context) = Simulator::connect(1);
+    let mut sampler = AnalogSampler::new(AnalogInput::new(1, context));
+    sampler
+        .set_filters(Channel::Channel0, &[FilterSpec::MovingAverage(2)])
+        .unwrap();
+    assert_eq!(
+        sampler.set_filters(
+            Channel::Channel1,
+            &[FilterSpec::ExponentialSmoothing(f64::NAN)]
+        ),
+        Err(FilterError::InvalidAlpha)
+    );
+
+    simulator.state.lock().unwrap().input_registers = [100; 8];
+    sampler.sample().await.unwrap();
+    simulator.state.lock().unwrap().input_registers = [200; 8];
+    let samples = sampler.sample().await.unwrap();
+    assert_eq!((samples[0].raw, samples[0].filtered), (200, 150.0));
+    assert_eq!((samples[1].raw, samples[1].filtered), (200, 200.0));
+    assert_eq!(sampler.latest().len(), 8);
+}
@@ -0,0 +1,225 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio_modbus::Request;
+use waveshare::analog_out::AnalogOutput;
+use waveshare::common::Channel;
+use waveshare::digital::{Action, DigitalIO};
+use waveshare::interlock::{ChannelRef, InterlockError, Interlocks, OutputKind, Rule};
+
+fn writes(simulator: &Simulator) -> usize {
+    simulator
+        .state
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .filter(|request| {
+            matches!(
+                request,
+                Request::WriteSingleCoil(..) | Request::WriteSingleRegister(..)
+            )
+        })
+        .count()
+}
+
+#[tokio::test(start_paused = true)]
+async fn mutually_exclusive_across_digital_and_analog() {
+    let (simulators, context) = Simulator::connect_bus(&[1, 2]);
+    let interlocks = Interlocks::new(context.clone());
+    let pump = ChannelRef::new(1, Channel::Channel0);
+    let heater = ChannelRef::new(2, Channel::Channel1);
+    interlocks
+        .add_rule(Rule::MutuallyExclusive {
+            first: (pump, OutputKind::Digital),
+            second: (heater, OutputKind::Analog),
+        })
+        .await;
+    let mut io: DigitalIO = DigitalIO::new(1, context.clone());
+    let mut output = AnalogOutput::new(2, context);
+
+    simulators[1]
+        .state
+        .lock()
+        .unwrap()
+        .holding_registers
+        .insert(1, 500);
+    assert!(matches!(
+        interlocks
+            .write_output_channel(&mut io, Channel::Channel0, Action::On)
+            .await,
+        Err(InterlockError::MutuallyExclusive { output, other })
+            if output == pump && other == heater
+    ));
+    assert_eq!(writes(&simulators[0]), 0);
+
+    interlocks
+        .write_output_channel_value(&mut output, Channel::Channel1, 0)
+        .await
+        .unwrap();
+    interlocks
+        .write_output_channel(&mut io, Channel::Channel0, Action::On)
+        .await
+        .unwrap();
+    assert!(simulators[0].state.lock().unwrap().coils[0]);
+
+    assert!(matches!(
+        interlocks
+            .write_output_channel_value(&mut output, Channel::Channel1, 300)
+            .await,
+        Err(InterlockError::MutuallyExclusive { output, other })
+            if output == heater && other == pump
+    ));
+    assert_eq!(writes(&simulators[1]), 1);
+    // Turning off is always allowed
+    interlocks
+        .write_output_channel_value(&mut output, Channel::Channel1, 0)
+        .await
+        .unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn permissive_is_read_from_another_unit() {
+    let (simulators, context) = Simulator::connect_bus(&[1, 2]);
+    let interlocks = Interlocks::new(context.clone());
+    let pump = ChannelRef::new(1, Channel::Channel0);
+    let level = ChannelRef::new(2, Channel::Channel3);
+    interlocks
+        .add_rule(Rule::Permissive {
+            output: pump,
+            input: level,
+            required: true,
+        })
+        .await;
+    let mut io: DigitalIO = DigitalIO::new(1, context);
+
+    assert!(matches!(
+        interlocks
+            .write_output_channel(&mut io, Channel::Channel0, Action::On)
+            .await,
+        Err(InterlockError::PermissiveNotMet { output, input, required: true })
+            if output == pump && input == level
+    ));
+    assert_eq!(writes(&simulators[0]), 0);
+    assert!(matches!(
+        simulators[1].state.lock().unwrap().requests[..],
+        [Request::ReadDiscreteInputs(3, 1)]
+    ));
+
+    simulators[1].state.lock().unwrap().inputs[3] = true;
+    interlocks
+        .write_output_channel(&mut io, Channel::Channel0, Action::On)
+        .await
+        .unwrap();
+    assert!(simulators[0].state.lock().unwrap().coils[0]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn minimum_on_and_off_times() {
+    let (simulator, context) = Simulator::connect(1);
+    context.set_silent_interval(Duration::ZERO).await;
+    let interlocks = Interlocks::new(context.clone());
+    let pump = ChannelRef::new(1, Channel::Channel0);
+    interlocks
+        .add_rule(Rule::MinimumTimes {
+            output: pump,
+            min_on: Duration::from_secs(10),
+            min_off: Duration::from_secs(5),
+        })
+        .await;
+    let mut io: DigitalIO = DigitalIO::new(1, context);
+
+    interlocks
+        .write_output_channel(&mut io, Channel::Channel0, Action::On)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    // Writing the current state again is not a change
+    interlocks
+        .write_output_channel(&mut io, Channel::Channel0, Action::On)
+        .await
+        .unwrap();
+    assert!(matches!(
+        interlocks
+            .write_output_channel(&mut io, Channel::Channel0, Action::Off)
+            .await,
+        Err(InterlockError::MinimumOnTime { remaining, .. })
+            if remaining == Duration::from_secs(8)
+    ));
+    assert_eq!(writes(&simulator), 2);
+
+    tokio::time::sleep(Duration::from_secs(8)).await;
+    interlocks
+        .write_output_channel(&mut io, Channel::Channel0, Action::Off)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(matches!(
+        interlocks
+            .write_output_channel(&mut io, Channel::Channel0, Action::On)
+            .await,
+        Err(InterlockError::MinimumOffTime { remaining, .. })
+            if remaining == Duration::from_secs(3)
+    ));
+    assert_eq!(writes(&simulator), 3);
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    interlocks
+        .write_output_channel(&mut io, Channel::Channel0, Action::On)
+        .await
+        .unwrap();
+    assert!(simulator.state.lock().unwrap().coils[0]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn starts_roll_off_after_an_hour() {
+    let (simulator, context) = Simulator::connect(1);
+    context.set_silent_interval(Duration::ZERO).await;
+    let interlocks = Interlocks::new(context.clone());
+    let pump = ChannelRef::new(1, Channel::Channel0);
+    interlocks
+        .add_rule(Rule::MaxStartsPerHour {
+            output: pump,
+            max_starts: 2,
+        })
+        .await;
+    let mut io: DigitalIO = DigitalIO::new(1, context);
+
+    for _ in 0..2 {
+        for action in [Action::On, Action::Off] {
+            interlocks
+                .write_output_channel(&mut io, Channel::Channel0, action)
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    let rejected = |result| {
+        matches!(
+            result,
+            Err(InterlockError::TooManyStarts { max_starts: 2, .. })
+        )
+    };
+    assert!(rejected(
+        interlocks
+            .write_output_channel(&mut io, Channel::Channel0, Action::On)
+            .await
+    ));
+
+    // The first start was 3598s ago
+    tokio::time::sleep(Duration::from_secs(3596)).await;
+    assert!(rejected(
+        interlocks
+            .write_output_channel(&mut io, Channel::Channel0, Action::On)
+            .await
+    ));
+    assert_eq!(writes(&simulator), 4);
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    interlocks
+        .write_output_channel(&mut io, Channel::Channel0, Action::On)
+        .await
+        .unwrap();
+    assert!(simulator.state.lock().unwrap().coils[0]);
+}
@@ -0,0 +1,171 @@
+use waveshare::common::Channel;
+use waveshare::digital::{Action, IoBank};
+
+fn bits(value: u8) -> [bool; 8] {
+    std::array::from_fn(|index| value & (1 << index) != 0)
+}
+
+#[test]
+fn u8_round_trip() {
+    for value in 0..=u8::MAX {
+        assert_eq!(u8::from(IoBank::from(value)), value);
+    }
+}
+
+#[test]
+fn index_matches_bits() {
+    for value in 0..=u8::MAX {
+        let bank = IoBank::from(value);
+        for (index, channel) in IoBank::<8>::CHANNELS.into_iter().enumerate() {
+            assert_eq!(bank[channel], value & (1 << index) != 0);
+            assert_eq!(bank.get(channel), bank[channel]);
+        }
+    }
+}
+
+#[test]
+fn set_and_index_mut() {
+    for value in 0..=u8::MAX {
+        for (index, channel) in IoBank::<8>::CHANNELS.into_iter().enumerate() {
+            let mut bank = IoBank::from(value);
+            bank.set(channel, true);
+            assert_eq!(u8::from(bank), value | (1 << index));
+            bank[channel] = false;
+            assert_eq!(u8::from(bank), value & !(1 << index));
+        }
+    }
+}
+
+#[test]
+fn iterates_set_channels_in_order() {
+    for value in 0..=u8::MAX {
+        let bank = IoBank::from(value);
+        let expected: Vec<Channel> = (0..8)
+            .filter(|index| value & (1 << index) != 0)
+            .map(|index| Channel::try_from(index).unwrap())
+            .collect();
+        assert_eq!(bank.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(bank.count(), value.count_ones());
+        assert_eq!(bank.is_empty(), value == 0);
+    }
+}
+
+#[test]
+fn bitwise_operators() {
+    for a in 0..=u8::MAX {
+        for b in 0..=u8::MAX {
+            let (x, y) = (IoBank::from(a), IoBank::from(b));
+            assert_eq!(u8::from(x & y), a & b);
+            assert_eq!(u8::from(x | y), a | b);
+            assert_eq!(u8::from(x ^ y), a ^ b);
+
+            let mut z = x;
+            z &= y;
+            assert_eq!(u8::from(z), a & b);
+            let mut z = x;
+            z |= y;
+            assert_eq!(u8::from(z), a | b);
+            let mut z = x;
+            z ^= y;
+            assert_eq!(u8::from(z), a ^ b);
+        }
+        assert_eq!(u8::from(!IoBank::from(a)), !a);
+    }
+}
+
+#[test]
+fn changed_channels() {
+    for a in 0..=u8::MAX {
+        for b in 0..=u8::MAX {
+            let changed: Vec<Channel> = IoBank::from(a).changed(&IoBank::from(b)).collect();
+            let expected: Vec<Channel> = IoBank::from(a ^ b).iter().collect();
+            assert_eq!(changed, expected);
+        }
+    }
+}
+
+#[test]
+fn bool_conversions() {
+    for value in 0..=u8::MAX {
+        let bank = IoBank::from(value);
+        assert_eq!(<[bool; 8]>::from(bank), bits(value));
+        assert_eq!(IoBank::from(bits(value)), bank);
+        assert_eq!(IoBank::from(bits(value).to_vec()), bank);
+    }
+}
+
+#[test]
+fn short_and_long_vecs() {
+    assert_eq!(IoBank::from(Vec::new()), IoBank::<8>::default());
+    assert_eq!(u8::from(IoBank::from(vec![true, false, true])), 0b101);
+    assert_eq!(u8::from(IoBank::from(vec![true; 16])), 0xFF);
+}
+
+#[test]
+fn display_as_binary() {
+    assert_eq!(IoBank::from(0u8).to_string(), "0b00000000");
+    assert_eq!(IoBank::from(0b1010_0101u8).to_string(), "0b10100101");
+    for value in 0..=u8::MAX {
+        assert_eq!(IoBank::from(value).to_string(), format!("0b{:08b}", value));
+    }
+}
+
+#[test]
+fn action_array_matches_bits() {
+    for value in 0..=u8::MAX {
+        let actions = IoBank::from(value).as_action_array();
+        for (action, bit) in actions.into_iter().zip(bits(value)) {
+            assert_eq!(action, if bit { Action::On } else { Action::Off });
+        }
+    }
+}
+
+#[test]
+fn wide_banks_pack_coils_lsb_first() {
+    let bank = IoBank::<16>::from(0xA55Au16);
+    assert_eq!(bank.to_bytes(), vec![0x5A, 0xA5]);
+    assert_eq!(IoBank::<16>::from_bytes(&[0x5A, 0xA5]), bank);
+    assert!(bank[Channel::Channel1]);
+    assert!(bank[Channel::Channel8]);
+    assert!(!bank[Channel::Channel9]);
+    assert!(bank[Channel::Channel15]);
+    assert_eq!(bank.to_string(), "0b1010010101011010");
+
+    let bank = IoBank::<32>::from(0x8000_0001u32);
+    assert_eq!(bank.to_bytes(), vec![0x01, 0x00, 0x00, 0x80]);
+    assert_eq!(
+        bank.iter().collect::<Vec<_>>(),
+        vec![Channel::Channel0, Channel::Channel31]
+    );
+    assert_eq!(u32::from(!bank), 0x7FFF_FFFE);
+}
+
+#[test]
+fn wide_bank_round_trips() {
+    for value in (0..=u16::MAX).step_by(7) {
+        let bank = IoBank::<16>::from(value);
+        assert_eq!(u16::from(bank), value);
+        assert_eq!(IoBank::<16>::from_bytes(&bank.to_bytes()), bank);
+        assert_eq!(IoBank::<16>::from(<[bool; 16]>::from(bank)), bank);
+    }
+    for value in [0, 1, 0xDEAD_BEEF, u32::MAX] {
+        let bank = IoBank::<32>::from(value);
+        assert_eq!(u32::from(bank), value);
+        assert_eq!(IoBank::<32>::from_bytes(&bank.to_bytes()), bank);
+    }
+}
+
+#[test]
+fn narrow_banks_ignore_extra_bits() {
+    assert_eq!(IoBank::<8>::from_bits(0x1FF), IoBank::from(0xFFu8));
+    assert_eq!(IoBank::<8>::from_bytes(&[0x0F, 0xFF]), IoBank::from(0x0Fu8));
+    assert_eq!(IoBank::<16>::from_bytes(&[0x01]), IoBank::from(0x0001u16));
+}
+
+#[test]
+fn channels_beyond_the_bank() {
+    let bank = IoBank::<8>::from(0xFFu8);
+    assert!(!bank.get(Channel::Channel8));
+    assert!(!bank.get(Channel::Channel31));
+    assert!(std::panic::catch_unwind(|| bank[Channel::Channel8]).is_err());
+}
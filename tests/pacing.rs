@@ -0,0 +1,22 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio::time::Instant;
+use waveshare::digital::DigitalIO;
+
+#[tokio::test(start_paused = true)]
+async fn clones_follow_pacing_set_after_they_were_taken() {
+    let (_simulator, context) = Simulator::connect(1);
+    let mut io: DigitalIO = DigitalIO::new(1, context.clone());
+
+    context.set_silent_interval(Duration::ZERO).await;
+    context
+        .set_turnaround_delay(1, Duration::from_millis(50))
+        .await;
+
+    io.read_output_channels().await.unwrap();
+    let started = Instant::now();
+    io.read_output_channels().await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(50));
+}
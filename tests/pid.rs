@@ -0,0 +1,117 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tokio_modbus::Request;
+use waveshare::analog_out::AnalogOutput;
+use waveshare::calibration::Calibration;
+use waveshare::common::Channel;
+use waveshare::pid::{Pid, PidError, PidLoop, Tunings};
+use waveshare::poller::{Quality, ScanValues, Snapshot};
+
+const PROPORTIONAL: Tunings = Tunings {
+    kp: 1.0,
+    ki: 0.0,
+    kd: 0.0,
+};
+
+fn sample(value: u16) -> Snapshot {
+    Snapshot {
+        values: ScanValues::Analog(vec![value; 8]),
+        timestamp: Some(Instant::now()),
+        quality: Quality::Good,
+        last_error: None,
+        overruns: 0,
+    }
+}
+
+#[test]
+fn initialized_output_is_held_at_zero_error() {
+    let mut pid = Pid::new(PROPORTIONAL, 0.0, 5000.0).unwrap();
+    pid.setpoint = 100.0;
+    pid.initialize(2500.0);
+    assert_eq!(pid.update(100.0, 0.0), 2500.0);
+    assert_eq!(pid.update(100.0, 0.1), 2500.0);
+    assert_eq!(pid.update(90.0, 0.1), 2510.0);
+}
+
+#[test]
+fn initialize_clamps_to_the_output_range() {
+    let mut pid = Pid::new(PROPORTIONAL, 0.0, 5000.0).unwrap();
+    pid.initialize(6000.0);
+    assert_eq!(pid.output(), 5000.0);
+}
+
+#[tokio::test]
+async fn loop_starts_from_the_actuator_without_a_bump() {
+    let (simulator, context) = Simulator::connect(1);
+    simulator
+        .state
+        .lock()
+        .unwrap()
+        .holding_registers
+        .insert(0, 2500);
+    let mut pid = Pid::new(PROPORTIONAL, 0.0, 5000.0).unwrap();
+    pid.setpoint = 1000.0;
+    let (process, snapshots) = watch::channel(Snapshot::default());
+    let (handle, _task) = PidLoop {
+        pid,
+        input_channel: Channel::Channel0,
+        input_scaling: Calibration::linear(0.0, 1.0),
+        output: AnalogOutput::new(1, context),
+        output_channel: Channel::Channel0,
+        output_scaling: Calibration::linear(0.0, 1.0),
+    }
+    .spawn(snapshots);
+
+    process.send(sample(1000)).unwrap();
+    wait_for(|| handle.status().process_value.is_some()).await;
+    assert_eq!(handle.status().output, 2500.0);
+    assert!(!simulator
+        .state
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .any(|request| matches!(request, Request::WriteSingleRegister(..))));
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    process.send(sample(1000)).unwrap();
+    wait_for(|| {
+        simulator
+            .state
+            .lock()
+            .unwrap()
+            .requests
+            .contains(&Request::WriteSingleRegister(0, 2500))
+    })
+    .await;
+}
+
+async fn wait_for(condition: impl Fn() -> bool) {
+    tokio::time::timeout(Duration::from_secs(1), async {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[test]
+fn limits_must_be_a_finite_range() {
+    for (min, max) in [
+        (5000.0, 0.0),
+        (f64::NAN, 5000.0),
+        (0.0, f64::NAN),
+        (0.0, f64::INFINITY),
+    ] {
+        assert!(matches!(
+            Pid::new(PROPORTIONAL, min, max),
+            Err(PidError::InvalidLimits(..))
+        ));
+    }
+    assert!(Pid::new(PROPORTIONAL, 100.0, 100.0).is_ok());
+}
@@ -0,0 +1,185 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio_modbus::Request;
+use waveshare::digital::Width;
+use waveshare::poller::{BusPoller, PollerError, Quality, ScanKind, ScanValues};
+
+fn count(simulator: &Simulator, matches: impl Fn(&Request<'static>) -> bool) -> usize {
+    simulator
+        .state
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .filter(|request| matches(request))
+        .count()
+}
+
+#[tokio::test(start_paused = true)]
+async fn each_scan_runs_at_its_own_rate() {
+    let (simulators, context) = Simulator::connect_bus(&[1, 2]);
+    context.set_silent_interval(Duration::ZERO).await;
+    let mut poller = BusPoller::new(context);
+    let _inputs = poller
+        .add_scan(
+            1,
+            ScanKind::DigitalInputs(Width::W8),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+    let _analog = poller
+        .add_scan(2, ScanKind::AnalogInputs, Duration::from_millis(250))
+        .unwrap();
+    let task = poller.spawn();
+
+    tokio::time::sleep(Duration::from_millis(990)).await;
+    task.abort();
+    // Both are scanned straight away, then once per period
+    assert_eq!(
+        count(&simulators[0], |request| matches!(
+            request,
+            Request::ReadDiscreteInputs(..)
+        )),
+        10
+    );
+    assert_eq!(
+        count(&simulators[1], |request| matches!(
+            request,
+            Request::ReadInputRegisters(..)
+        )),
+        4
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn failed_scans_keep_the_last_good_values() {
+    let (simulator, context) = Simulator::connect(1);
+    simulator.state.lock().unwrap().input_registers = [1, 2, 3, 4, 5, 6, 7, 8];
+    let mut poller = BusPoller::new(context);
+    let mut snapshots = poller
+        .add_scan(1, ScanKind::AnalogInputs, Duration::from_millis(100))
+        .unwrap();
+    poller.spawn();
+
+    snapshots.changed().await.unwrap();
+    let good = snapshots.borrow_and_update().clone();
+    assert_eq!(good.quality, Quality::Good);
+    assert_eq!(
+        good.values,
+        ScanValues::Analog(vec![1, 2, 3, 4, 5, 6, 7, 8])
+    );
+    assert!(good.last_error.is_none());
+
+    {
+        let mut state = simulator.state.lock().unwrap();
+        state.failing = true;
+        state.input_registers = [0; 8];
+    }
+    snapshots.changed().await.unwrap();
+    let bad = snapshots.borrow_and_update().clone();
+    assert_eq!(bad.quality, Quality::Bad);
+    assert_eq!(bad.values, good.values);
+    assert_eq!(bad.timestamp, good.timestamp);
+    assert!(bad.last_error.is_some());
+
+    simulator.state.lock().unwrap().failing = false;
+    snapshots.changed().await.unwrap();
+    let recovered = snapshots.borrow_and_update().clone();
+    assert_eq!(recovered.quality, Quality::Good);
+    assert_eq!(recovered.values, ScanValues::Analog(vec![0; 8]));
+}
+
+#[tokio::test(start_paused = true)]
+async fn late_scans_are_reported_as_overruns() {
+    let (_simulator, context) = Simulator::connect(1);
+    // Every request keeps the bus for longer than the scan period
+    context
+        .set_turnaround_delay(1, Duration::from_millis(250))
+        .await;
+    let mut poller = BusPoller::new(context);
+    let mut overruns = poller.subscribe_overruns();
+    let snapshots = poller
+        .add_scan(
+            1,
+            ScanKind::DigitalOutputs(Width::W8),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+    poller.spawn();
+
+    let overrun = overruns.recv().await.unwrap();
+    assert_eq!(overrun.unit_id, 1);
+    assert_eq!(overrun.kind, ScanKind::DigitalOutputs(Width::W8));
+    assert!(overrun.missed_scans >= 1);
+    assert!(overrun.late_by >= Duration::from_millis(100));
+    assert!(snapshots.borrow().overruns >= 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn stops_once_every_receiver_is_dropped() {
+    let (_simulator, context) = Simulator::connect(1);
+    let mut poller = BusPoller::new(context);
+    let inputs = poller
+        .add_scan(
+            1,
+            ScanKind::DigitalInputs(Width::W8),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+    let outputs = poller
+        .add_scan(
+            1,
+            ScanKind::DigitalOutputs(Width::W8),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+    let task = poller.spawn();
+
+    drop(inputs);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!task.is_finished());
+    drop(outputs);
+    tokio::time::timeout(Duration::from_secs(1), task)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn zero_period_is_rejected() {
+    let (_simulator, context) = Simulator::connect(1);
+    let mut poller = BusPoller::new(context);
+    assert!(matches!(
+        poller.add_scan(1, ScanKind::DigitalInputs(Width::W8), Duration::ZERO),
+        Err(PollerError::InvalidPeriod)
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn digital_scans_read_the_whole_board() {
+    let (simulator, context) = Simulator::connect(1);
+    let mut poller = BusPoller::new(context);
+    let _inputs = poller
+        .add_scan(
+            1,
+            ScanKind::DigitalInputs(Width::W16),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+    let _outputs = poller
+        .add_scan(
+            1,
+            ScanKind::DigitalOutputs(Width::W32),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+    let task = poller.spawn();
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    task.abort();
+    let state = simulator.state.lock().unwrap();
+    assert!(state.requests.contains(&Request::ReadDiscreteInputs(0, 16)));
+    assert!(state.requests.contains(&Request::ReadCoils(0, 32)));
+}
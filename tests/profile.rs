@@ -0,0 +1,34 @@
+mod simulator;
+
+use simulator::Simulator;
+use waveshare::profile::{DeviceProfile, ProfileError, ProfiledDevice};
+
+#[test]
+fn blocks_past_the_last_address_fail_to_parse() {
+    let profile = "name relay\nblock outputs coil 0xFFF0 17 bool\n";
+    assert!(matches!(
+        profile.parse::<DeviceProfile>(),
+        Err(ProfileError::Parse(2, _))
+    ));
+
+    let profile = "name relay\nblock outputs coil 0xFFF0 16 bool\n";
+    assert!(profile.parse::<DeviceProfile>().is_ok());
+}
+
+#[tokio::test]
+async fn channel_blocks_past_the_last_address_are_out_of_range() {
+    let (simulator, context) = Simulator::connect(1);
+    let profile = "name relay\nchannels 8\nblock outputs coil 0xFFFC channels bool\n"
+        .parse::<DeviceProfile>()
+        .unwrap();
+    let mut device = ProfiledDevice::new(1, context, profile);
+    assert!(matches!(
+        device.read("outputs").await,
+        Err(ProfileError::OutOfRange { .. })
+    ));
+    assert!(matches!(
+        device.read_index("outputs", 5).await,
+        Err(ProfileError::OutOfRange { .. })
+    ));
+    assert!(simulator.state.lock().unwrap().requests.is_empty());
+}
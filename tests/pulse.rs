@@ -0,0 +1,98 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio_modbus::Request;
+use waveshare::common::Channel;
+use waveshare::digital::DigitalIO;
+use waveshare::pulse::{pulse_train, PulseError, PulseOutcome, PulseTrain};
+
+const PERIOD: Duration = Duration::from_millis(100);
+
+// Coil writes to channel 0 in the order the device received them
+fn writes(simulator: &Simulator) -> Vec<bool> {
+    simulator
+        .state
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .filter_map(|request| match request {
+            Request::WriteSingleCoil(0, value) => Some(*value),
+            _ => None,
+        })
+        .collect()
+}
+
+async fn run_pwm(duty: f64) -> (Simulator, PulseOutcome) {
+    let (simulator, context) = Simulator::connect(1);
+    let io = DigitalIO::new(1, context);
+    let mut handle = pulse_train(
+        &io,
+        Channel::Channel0,
+        PulseTrain::pwm(PERIOD, duty).unwrap(),
+    )
+    .unwrap();
+    // Stops a quarter of the way into the eleventh period
+    tokio::time::sleep(PERIOD * 10 + PERIOD / 4).await;
+    handle.stop();
+    let outcome = handle.finished().await.unwrap();
+    (simulator, outcome)
+}
+
+#[tokio::test(start_paused = true)]
+async fn zero_duty_never_switches_on() {
+    let (simulator, outcome) = run_pwm(0.0).await;
+    assert!(matches!(outcome, PulseOutcome::Stopped { .. }));
+    assert_eq!(writes(&simulator), vec![false]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn full_duty_stays_on_until_stopped() {
+    let (simulator, outcome) = run_pwm(1.0).await;
+    assert!(matches!(outcome, PulseOutcome::Stopped { .. }));
+    assert_eq!(writes(&simulator), vec![true, false]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn half_duty_switches_every_half_period() {
+    let (simulator, _) = run_pwm(0.5).await;
+    let writes = writes(&simulator);
+    assert_eq!(writes.len(), 22);
+    assert!(writes
+        .iter()
+        .enumerate()
+        .all(|(index, value)| *value == (index % 2 == 0)));
+}
+
+#[tokio::test(start_paused = true)]
+async fn counted_trains_finish_off() {
+    let (simulator, context) = Simulator::connect(1);
+    let io = DigitalIO::new(1, context);
+    let train = PulseTrain::new(3, PERIOD, Duration::ZERO);
+    let handle = pulse_train(&io, Channel::Channel0, train).unwrap();
+    assert_eq!(
+        handle.finished().await.unwrap(),
+        PulseOutcome::Completed { pulses: 3 }
+    );
+    assert_eq!(writes(&simulator), vec![true, false]);
+}
+
+#[tokio::test]
+async fn zero_periods_are_rejected() {
+    let (_simulator, context) = Simulator::connect(1);
+    let io = DigitalIO::new(1, context);
+    assert!(matches!(
+        PulseTrain::pwm(Duration::ZERO, 0.5),
+        Err(PulseError::InvalidPeriod)
+    ));
+    let train = PulseTrain {
+        count: None,
+        on: Duration::ZERO,
+        off: Duration::ZERO,
+    };
+    assert!(matches!(
+        pulse_train(&io, Channel::Channel0, train),
+        Err(PulseError::InvalidPeriod)
+    ));
+}
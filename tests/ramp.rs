@@ -0,0 +1,88 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio_modbus::Request;
+use waveshare::analog_out::AnalogOutput;
+use waveshare::common::Channel;
+use waveshare::ramp::{RampError, RampOutcome, RampRate, Ramper};
+
+#[tokio::test(start_paused = true)]
+async fn ramps_complete() {
+    let (simulator, context) = Simulator::connect(1);
+    let ramper = Ramper::spawn(AnalogOutput::new(1, context), Duration::from_millis(50)).unwrap();
+    let handle = ramper.ramp(
+        Channel::Channel2,
+        1000,
+        RampRate::Over(Duration::from_secs(1)),
+    );
+    assert_eq!(handle.finished().await.unwrap(), RampOutcome::Completed);
+    assert_eq!(
+        simulator.state.lock().unwrap().holding_registers.get(&2),
+        Some(&1000)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn ramps_finish_after_every_sender_is_dropped() {
+    let (simulator, context) = Simulator::connect(1);
+    let ramper = Ramper::spawn(AnalogOutput::new(1, context), Duration::from_millis(50)).unwrap();
+    let handle = ramper.ramp(Channel::Channel0, 500, RampRate::PerSecond(1000.0));
+    drop(handle);
+    drop(ramper);
+
+    // Time only advances while the ramp task is idle, a spinning task hangs here
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(
+        simulator.state.lock().unwrap().holding_registers.get(&0),
+        Some(&500)
+    );
+}
+
+#[tokio::test]
+async fn zero_update_period_is_rejected() {
+    let (_simulator, context) = Simulator::connect(1);
+    assert!(matches!(
+        Ramper::spawn(AnalogOutput::new(1, context), Duration::ZERO),
+        Err(RampError::InvalidUpdatePeriod)
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn rates_too_slow_for_a_duration_are_rejected() {
+    let (_simulator, context) = Simulator::connect(1);
+    let ramper = Ramper::spawn(AnalogOutput::new(1, context), Duration::from_millis(50)).unwrap();
+    let handle = ramper.ramp(
+        Channel::Channel1,
+        4000,
+        RampRate::PerSecond(f64::MIN_POSITIVE),
+    );
+    assert!(matches!(
+        handle.finished().await,
+        Err(RampError::InvalidRate(RampRate::PerSecond(_)))
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn rates_that_would_step_the_output_are_rejected() {
+    let (simulator, context) = Simulator::connect(1);
+    let ramper = Ramper::spawn(AnalogOutput::new(1, context), Duration::from_millis(50)).unwrap();
+    for per_second in [0.0, -100.0, f64::NAN, f64::INFINITY] {
+        let handle = ramper.ramp(Channel::Channel1, 4000, RampRate::PerSecond(per_second));
+        assert!(matches!(
+            handle.finished().await,
+            Err(RampError::InvalidRate(RampRate::PerSecond(_)))
+        ));
+    }
+    // Nothing was written, only the starting value read
+    assert!(!simulator
+        .state
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .any(|request| matches!(
+            request,
+            Request::WriteSingleRegister(..) | Request::WriteMultipleRegisters(..)
+        )));
+}
@@ -0,0 +1,205 @@
+mod simulator;
+
+use simulator::Simulator;
+use tokio_modbus::Request;
+use waveshare::analog_in::AnalogInput;
+use waveshare::analog_out::AnalogOutput;
+use waveshare::common::Channel;
+use waveshare::digital::{AnyDigitalIO, DigitalIO, Width};
+use waveshare::registry::{
+    Device, Direction, RegistryError, TagDefinition, TagRegistry, TagType, TagValue,
+};
+
+// `io` is unit 1, `ain` unit 2 and `aout` unit 3
+fn registry() -> (Vec<Simulator>, TagRegistry) {
+    let (simulators, context) = Simulator::connect_bus(&[1, 2, 3]);
+    let mut registry = TagRegistry::new();
+    let io: DigitalIO = DigitalIO::new(1, context.clone());
+    registry.add_device("io", Device::DigitalIO(io.into()));
+    registry.add_device(
+        "ain",
+        Device::AnalogInput(AnalogInput::new(2, context.clone())),
+    );
+    registry.add_device("aout", Device::AnalogOutput(AnalogOutput::new(3, context)));
+    (simulators, registry)
+}
+
+fn channel(index: usize) -> Channel {
+    Channel::try_from(index as u8).unwrap()
+}
+
+#[tokio::test(start_paused = true)]
+async fn tags_on_one_device_share_a_read() {
+    let (simulators, mut registry) = registry();
+    simulators[0].state.lock().unwrap().coils[3] = true;
+    simulators[1].state.lock().unwrap().input_registers = [10, 11, 12, 13, 14, 15, 16, 17];
+    let mut names = Vec::new();
+    for index in 0..10 {
+        let (coil, input) = (format!("coil{}", index), format!("level{}", index));
+        registry
+            .add_tag(
+                &coil,
+                TagDefinition::new(
+                    "io",
+                    channel(index % 8),
+                    Direction::Output,
+                    TagType::Digital,
+                ),
+            )
+            .unwrap();
+        registry
+            .add_tag(
+                &input,
+                TagDefinition::new("ain", channel(index % 8), Direction::Input, TagType::Analog),
+            )
+            .unwrap();
+        names.extend([coil, input]);
+    }
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let values = registry.read_tags(&names).await.unwrap();
+
+    assert_eq!(values.len(), 20);
+    assert_eq!(values["coil3"], TagValue::Digital(true));
+    assert_eq!(values["coil4"], TagValue::Digital(false));
+    assert_eq!(values["level1"], TagValue::Analog(11));
+    assert_eq!(values["level9"], TagValue::Analog(11));
+    assert_eq!(
+        simulators[0].state.lock().unwrap().requests,
+        [Request::ReadCoils(0, 8)]
+    );
+    assert_eq!(
+        simulators[1].state.lock().unwrap().requests,
+        [Request::ReadInputRegisters(0, 8)]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn tags_are_written_to_their_channel() {
+    let (simulators, mut registry) = registry();
+    registry
+        .add_tag(
+            "pump",
+            TagDefinition::new("io", Channel::Channel5, Direction::Output, TagType::Digital),
+        )
+        .unwrap();
+    registry
+        .add_tag(
+            "valve",
+            TagDefinition::new(
+                "aout",
+                Channel::Channel2,
+                Direction::Output,
+                TagType::Analog,
+            ),
+        )
+        .unwrap();
+
+    registry
+        .write_tag("pump", TagValue::Digital(true))
+        .await
+        .unwrap();
+    registry
+        .write_tag("valve", TagValue::Analog(2500))
+        .await
+        .unwrap();
+    assert!(simulators[0].state.lock().unwrap().coils[5]);
+    assert_eq!(
+        simulators[2]
+            .state
+            .lock()
+            .unwrap()
+            .holding_registers
+            .get(&2),
+        Some(&2500)
+    );
+    assert_eq!(
+        registry.read_tag("pump").await.unwrap(),
+        TagValue::Digital(true)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn invalid_tags_are_refused_without_touching_the_bus() {
+    let (simulators, mut registry) = registry();
+    registry
+        .add_tag(
+            "start",
+            TagDefinition::new("io", Channel::Channel0, Direction::Input, TagType::Digital),
+        )
+        .unwrap();
+    registry
+        .add_tag(
+            "pump",
+            TagDefinition::new("io", Channel::Channel1, Direction::Output, TagType::Digital),
+        )
+        .unwrap();
+
+    assert!(matches!(
+        registry.add_tag(
+            "speed",
+            TagDefinition::new("io", Channel::Channel1, Direction::Output, TagType::Analog),
+        ),
+        Err(RegistryError::UnsupportedTag(name)) if name == "speed"
+    ));
+    assert!(matches!(
+        registry.add_tag(
+            "setpoint",
+            TagDefinition::new("ain", Channel::Channel1, Direction::Output, TagType::Analog),
+        ),
+        Err(RegistryError::UnsupportedTag(_))
+    ));
+    assert!(matches!(
+        registry.add_tag(
+            "flow",
+            TagDefinition::new("meter", Channel::Channel1, Direction::Input, TagType::Analog),
+        ),
+        Err(RegistryError::UnknownDevice(name)) if name == "meter"
+    ));
+    assert!(matches!(
+        registry.write_tag("start", TagValue::Digital(true)).await,
+        Err(RegistryError::ReadOnly(name)) if name == "start"
+    ));
+    assert!(matches!(
+        registry.write_tag("pump", TagValue::Analog(1)).await,
+        Err(RegistryError::TypeMismatch(name)) if name == "pump"
+    ));
+    assert!(matches!(
+        registry.write_tag("fan", TagValue::Digital(true)).await,
+        Err(RegistryError::UnknownTag(_))
+    ));
+    assert!(matches!(
+        registry.read_tags(&["pump", "fan"]).await,
+        Err(RegistryError::UnknownTag(name)) if name == "fan"
+    ));
+    for simulator in &simulators {
+        assert!(simulator.state.lock().unwrap().requests.is_empty());
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn wide_boards_read_every_channel() {
+    let (simulator, context) = Simulator::connect(1);
+    let mut registry = TagRegistry::new();
+    registry.add_device(
+        "io",
+        Device::DigitalIO(AnyDigitalIO::new(1, context, Width::W16)),
+    );
+    registry
+        .add_tag(
+            "valve",
+            TagDefinition::new(
+                "io",
+                Channel::Channel12,
+                Direction::Output,
+                TagType::Digital,
+            ),
+        )
+        .unwrap();
+
+    // The simulated board only has 8 coils and refuses the wider read
+    assert!(registry.read_tag("valve").await.is_err());
+    assert_eq!(
+        simulator.state.lock().unwrap().requests,
+        [Request::ReadCoils(0, 16)]
+    );
+}
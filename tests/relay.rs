@@ -0,0 +1,88 @@
+mod simulator;
+
+use simulator::Simulator;
+use tokio_modbus::Request;
+use waveshare::common::Channel;
+use waveshare::digital::{DigitalIOError, IoBank};
+use waveshare::relay::{Relay, RelayError, RelayModel};
+
+#[tokio::test(start_paused = true)]
+async fn toggling_flips_one_relay() {
+    let (simulator, context) = Simulator::connect(1);
+    simulator.state.lock().unwrap().coils = IoBank::from(0b0000_0100).into();
+    let mut relay = Relay::new(1, context, RelayModel::Standard);
+
+    assert!(!relay.toggle_relay(Channel::Channel2).await.unwrap());
+    assert!(relay.toggle_relay(Channel::Channel5).await.unwrap());
+    let state = simulator.state.lock().unwrap();
+    assert_eq!(state.coils, <[bool; 8]>::from(IoBank::from(0b0010_0000)));
+    assert!(matches!(
+        state.requests[..],
+        [
+            Request::ReadCoils(0, 8),
+            Request::WriteMultipleCoils(0, _),
+            Request::ReadCoils(0, 8),
+            Request::WriteMultipleCoils(0, _),
+        ]
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn toggling_a_missing_relay_is_refused() {
+    let (simulator, context) = Simulator::connect(1);
+    let mut relay = Relay::new(1, context, RelayModel::Standard);
+    assert!(matches!(
+        relay.toggle_relay(Channel::Channel8).await,
+        Err(RelayError::DigitalIO(DigitalIOError::InvalidChannel(
+            Channel::Channel8
+        )))
+    ));
+    assert!(simulator.state.lock().unwrap().requests.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn toggling_all_flips_every_relay() {
+    let (simulator, context) = Simulator::connect(1);
+    simulator.state.lock().unwrap().coils = IoBank::from(0b0000_0101).into();
+    let mut relay = Relay::new(1, context, RelayModel::Standard);
+
+    assert_eq!(
+        relay.toggle_all_relays().await.unwrap(),
+        IoBank::from(0b1111_1010)
+    );
+    assert_eq!(
+        simulator.state.lock().unwrap().coils,
+        <[bool; 8]>::from(IoBank::from(0b1111_1010))
+    );
+    assert_eq!(
+        relay.read_relays().await.unwrap(),
+        IoBank::from(0b1111_1010)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn standard_boards_have_no_inputs() {
+    let (simulator, context) = Simulator::connect(1);
+    let mut relay = Relay::new(1, context, RelayModel::Standard);
+    assert!(matches!(
+        relay.read_input_status(Channel::Channel0).await,
+        Err(RelayError::NoInputs)
+    ));
+    assert!(matches!(
+        relay.read_inputs().await,
+        Err(RelayError::NoInputs)
+    ));
+    assert!(simulator.state.lock().unwrap().requests.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn boards_with_inputs_read_them() {
+    let (simulator, context) = Simulator::connect(1);
+    simulator.state.lock().unwrap().inputs[6] = true;
+    let mut relay = Relay::new(1, context, RelayModel::WithInputs);
+    assert!(relay.read_input_status(Channel::Channel6).await.unwrap());
+    assert_eq!(
+        relay.read_inputs().await.unwrap(),
+        IoBank::from(0b0100_0000)
+    );
+}
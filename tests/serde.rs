@@ -0,0 +1,118 @@
+#![cfg(feature = "serde")]
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use std::fmt::Debug;
+use waveshare::common::{Baudrates, Channel, DeviceSettings, Parity, UartParameters};
+use waveshare::digital::{Action, DigitalIOSnapshot, IoBank};
+use waveshare::{analog_in, analog_out, digital};
+
+fn round_trip<T>(value: T, expected: serde_json::Value)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let serialised = serde_json::to_value(&value).unwrap();
+    assert_eq!(serialised, expected);
+    assert_eq!(serde_json::from_value::<T>(serialised).unwrap(), value);
+}
+
+#[test]
+fn channels_are_indices() {
+    for (index, channel) in Channel::ALL.into_iter().enumerate() {
+        round_trip(channel, json!(index));
+    }
+    assert!(serde_json::from_value::<Channel>(json!(32)).is_err());
+}
+
+#[test]
+fn actions() {
+    round_trip(Action::On, json!("on"));
+    round_trip(Action::Off, json!("off"));
+    assert!(serde_json::from_value::<Action>(json!("On")).is_err());
+}
+
+#[test]
+fn baudrates_are_bits_per_second() {
+    for (baudrate, bps) in [
+        (Baudrates::B4800, 4800),
+        (Baudrates::B9600, 9600),
+        (Baudrates::B19200, 19200),
+        (Baudrates::B38400, 38400),
+        (Baudrates::B57600, 57600),
+        (Baudrates::B115200, 115200),
+        (Baudrates::B128000, 128000),
+        (Baudrates::B256000, 256000),
+    ] {
+        round_trip(baudrate, json!(bps));
+    }
+    assert!(serde_json::from_value::<Baudrates>(json!(1200)).is_err());
+}
+
+#[test]
+fn parity() {
+    round_trip(Parity::None, json!("none"));
+    round_trip(Parity::Even, json!("even"));
+    round_trip(Parity::Odd, json!("odd"));
+}
+
+#[test]
+fn digital_control_modes() {
+    round_trip(digital::ControlMode::Command, json!("command"));
+    round_trip(digital::ControlMode::Linked, json!("linked"));
+    round_trip(digital::ControlMode::Flip, json!("flip"));
+}
+
+#[test]
+fn analog_control_modes() {
+    let names = ["0-10V", "2-10V", "0-20mA", "4-20mA", "raw"];
+    for (raw, name) in names.into_iter().enumerate() {
+        round_trip(
+            analog_in::ControlMode::from_u16(raw as u16).unwrap(),
+            json!(name),
+        );
+        round_trip(
+            analog_out::ControlMode::from_u16(raw as u16).unwrap(),
+            json!(name),
+        );
+    }
+}
+
+#[test]
+fn io_banks_are_bool_lists() {
+    round_trip(
+        IoBank::from(0b0000_0101u8),
+        json!([true, false, true, false, false, false, false, false]),
+    );
+    round_trip(IoBank::from(0xFFFFu16), json!(vec![true; 16]));
+    for value in 0..=u8::MAX {
+        let bank = IoBank::from(value);
+        let serialised = serde_json::to_string(&bank).unwrap();
+        assert_eq!(serde_json::from_str::<IoBank>(&serialised).unwrap(), bank);
+    }
+    assert!(serde_json::from_value::<IoBank>(json!([true, false])).is_err());
+}
+
+#[test]
+fn snapshots() {
+    round_trip(
+        DigitalIOSnapshot {
+            settings: DeviceSettings {
+                uart: UartParameters {
+                    baudrate: Baudrates::B115200,
+                    parity: Parity::Even,
+                },
+                address: 3,
+                software_version: 0x0100,
+            },
+            control_modes: vec![digital::ControlMode::Linked; 8],
+        },
+        json!({
+            "settings": {
+                "uart": { "baudrate": 115200, "parity": "even" },
+                "address": 3,
+                "software_version": 256,
+            },
+            "control_modes": vec!["linked"; 8],
+        }),
+    );
+}
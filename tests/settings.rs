@@ -0,0 +1,101 @@
+mod simulator;
+
+use simulator::Simulator;
+use tokio_modbus::Request;
+use waveshare::common::{
+    Baudrates, CommonHoldingRegisters, DeviceSettings, Parity, SettingsError, UartParameters,
+    WaveshareModbus,
+};
+use waveshare::digital::{DigitalIO, DigitalIOError};
+
+// A driver written against the trait before the settings reads existed
+struct LegacyDriver;
+
+#[derive(Debug)]
+enum LegacyError {
+    Settings(SettingsError),
+}
+
+impl From<SettingsError> for LegacyError {
+    fn from(err: SettingsError) -> Self {
+        LegacyError::Settings(err)
+    }
+}
+
+impl WaveshareModbus for LegacyDriver {
+    type Error = LegacyError;
+
+    async fn set_uart_parameters(&mut self, _: Baudrates, _: Parity) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn set_device_address(&mut self, _: u8) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn read_software_version(&mut self) -> Result<u16, Self::Error> {
+        Ok(1)
+    }
+}
+
+#[tokio::test]
+async fn drivers_without_register_reads_are_unsupported() {
+    assert!(matches!(
+        DeviceSettings::read(&mut LegacyDriver).await,
+        Err(LegacyError::Settings(SettingsError::Unsupported))
+    ));
+}
+
+#[tokio::test]
+async fn restore_writes_only_changed_settings() {
+    let (simulator, context) = Simulator::connect(1);
+    let uart = UartParameters {
+        baudrate: Baudrates::B9600,
+        parity: Parity::None,
+    };
+    {
+        let mut state = simulator.state.lock().unwrap();
+        state
+            .holding_registers
+            .insert(CommonHoldingRegisters::DeviceAddress as u16, 1);
+        state.holding_registers.insert(
+            CommonHoldingRegisters::UartParameters as u16,
+            uart.to_register(),
+        );
+    }
+    let mut io: DigitalIO = DigitalIO::new(1, context);
+    let mut settings = DeviceSettings::read(&mut io).await.unwrap();
+    assert_eq!(settings.uart, uart);
+
+    settings.uart.baudrate = Baudrates::B115200;
+    io.restore_settings(&settings).await.unwrap();
+    let state = simulator.state.lock().unwrap();
+    assert_eq!(
+        state.holding_registers[&(CommonHoldingRegisters::UartParameters as u16)],
+        settings.uart.to_register()
+    );
+    // The address already matched, so it is left alone
+    assert!(!state.requests.iter().any(|request| matches!(
+        request,
+        Request::WriteSingleRegister(address, _)
+            if *address == CommonHoldingRegisters::DeviceAddress as u16
+    )));
+}
+
+#[tokio::test]
+async fn invalid_uart_parameters_are_reported() {
+    let (simulator, context) = Simulator::connect(1);
+    simulator
+        .state
+        .lock()
+        .unwrap()
+        .holding_registers
+        .insert(CommonHoldingRegisters::UartParameters as u16, 0x0309);
+    let mut io: DigitalIO = DigitalIO::new(1, context);
+    assert!(matches!(
+        io.read_uart_parameters().await,
+        Err(DigitalIOError::Settings(
+            SettingsError::InvalidUartParameters(0x0309)
+        ))
+    ));
+}
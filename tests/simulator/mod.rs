@@ -0,0 +1,239 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio_modbus::{Request, Slave};
+use waveshare::ThreadSafeContext;
+
+// Control all outputs coil on the IO module
+const CONTROL_ALL: u16 = 0x00FF;
+
+const ILLEGAL_FUNCTION: u8 = 0x01;
+const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+const SERVER_DEVICE_FAILURE: u8 = 0x04;
+
+#[derive(Debug, Default)]
+pub struct DeviceState {
+    pub coils: [bool; 8],
+    pub inputs: [bool; 8],
+    pub input_registers: [u16; 8],
+    pub holding_registers: HashMap<u16, u16>,
+    // Every request fails with an exception while set
+    pub failing: bool,
+    // The next this many requests fail with an exception
+    pub fail_next: usize,
+    // Every request this device acted on, including broadcasts
+    pub requests: Vec<Request<'static>>,
+}
+
+impl DeviceState {
+    // Returns the response PDU after the function code, or an exception code
+    fn handle(&mut self, request: Request<'static>) -> Result<Vec<u8>, u8> {
+        self.requests.push(request.clone());
+        if self.failing || self.fail_next > 0 {
+            self.fail_next = self.fail_next.saturating_sub(1);
+            return Err(SERVER_DEVICE_FAILURE);
+        }
+        match request {
+            Request::ReadCoils(addr, cnt) => Ok(pack_bits(&slice(&self.coils, addr, cnt)?)),
+            Request::ReadDiscreteInputs(addr, cnt) => {
+                Ok(pack_bits(&slice(&self.inputs, addr, cnt)?))
+            }
+            Request::ReadInputRegisters(addr, cnt) => {
+                Ok(pack_words(&slice(&self.input_registers, addr, cnt)?))
+            }
+            Request::ReadHoldingRegisters(addr, cnt) => Ok(pack_words(
+                &(addr..addr + cnt)
+                    .map(|addr| self.holding_registers.get(&addr).copied().unwrap_or(0))
+                    .collect::<Vec<_>>(),
+            )),
+            Request::WriteSingleCoil(CONTROL_ALL, value) => {
+                self.coils = [value; 8];
+                Ok(echo(CONTROL_ALL, if value { 0xFF00 } else { 0x0000 }))
+            }
+            Request::WriteSingleCoil(addr, value) => {
+                *self
+                    .coils
+                    .get_mut(addr as usize)
+                    .ok_or(ILLEGAL_DATA_ADDRESS)? = value;
+                Ok(echo(addr, if value { 0xFF00 } else { 0x0000 }))
+            }
+            Request::WriteMultipleCoils(addr, values) => {
+                let end = addr as usize + values.len();
+                self.coils
+                    .get_mut(addr as usize..end)
+                    .ok_or(ILLEGAL_DATA_ADDRESS)?
+                    .copy_from_slice(&values);
+                Ok(echo(addr, values.len() as u16))
+            }
+            Request::WriteSingleRegister(addr, value) => {
+                self.holding_registers.insert(addr, value);
+                Ok(echo(addr, value))
+            }
+            Request::WriteMultipleRegisters(addr, values) => {
+                for (offset, value) in values.iter().enumerate() {
+                    self.holding_registers.insert(addr + offset as u16, *value);
+                }
+                Ok(echo(addr, values.len() as u16))
+            }
+            _ => Err(ILLEGAL_FUNCTION),
+        }
+    }
+}
+
+fn slice<T: Copy>(values: &[T], addr: u16, cnt: u16) -> Result<Vec<T>, u8> {
+    values
+        .get(addr as usize..addr as usize + cnt as usize)
+        .map(<[T]>::to_vec)
+        .ok_or(ILLEGAL_DATA_ADDRESS)
+}
+
+fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    let mut data = vec![0; bits.len().div_ceil(8)];
+    for (index, bit) in bits.iter().enumerate() {
+        data[index / 8] |= u8::from(*bit) << (index % 8);
+    }
+    data.insert(0, data.len() as u8);
+    data
+}
+
+fn pack_words(words: &[u16]) -> Vec<u8> {
+    let mut data = vec![(words.len() * 2) as u8];
+    data.extend(words.iter().flat_map(|word| word.to_be_bytes()));
+    data
+}
+
+fn echo(addr: u16, value: u16) -> Vec<u8> {
+    [addr.to_be_bytes(), value.to_be_bytes()].concat()
+}
+
+fn crc(frame: &[u8]) -> u16 {
+    let mut crc = 0xFFFF;
+    for byte in frame {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn word(data: &[u8], index: usize) -> u16 {
+    u16::from_be_bytes([data[index], data[index + 1]])
+}
+
+fn decode(function: u8, data: &[u8]) -> Option<Request<'static>> {
+    let (addr, value) = (word(data, 0), word(data, 2));
+    let request = match function {
+        0x01 => Request::ReadCoils(addr, value),
+        0x02 => Request::ReadDiscreteInputs(addr, value),
+        0x03 => Request::ReadHoldingRegisters(addr, value),
+        0x04 => Request::ReadInputRegisters(addr, value),
+        0x05 => Request::WriteSingleCoil(addr, value == 0xFF00),
+        0x06 => Request::WriteSingleRegister(addr, value),
+        0x0F => Request::WriteMultipleCoils(
+            addr,
+            Cow::Owned(
+                (0..value as usize)
+                    .map(|index| data[5 + index / 8] & (1 << (index % 8)) != 0)
+                    .collect(),
+            ),
+        ),
+        0x10 => Request::WriteMultipleRegisters(
+            addr,
+            Cow::Owned(
+                (0..value as usize)
+                    .map(|index| word(data, 5 + index * 2))
+                    .collect(),
+            ),
+        ),
+        _ => return None,
+    };
+    Some(request)
+}
+
+// Serves RTU frames until the client side is dropped. Devices answer their own
+// unit id, unit 0 reaches every device and is never answered.
+async fn serve(mut stream: DuplexStream, devices: HashMap<u8, Simulator>) {
+    loop {
+        let mut frame = vec![0; 6];
+        if stream.read_exact(&mut frame).await.is_err() {
+            return;
+        }
+        let rest = match frame[1] {
+            0x0F | 0x10 => {
+                let mut count = [0];
+                if stream.read_exact(&mut count).await.is_err() {
+                    return;
+                }
+                frame.push(count[0]);
+                count[0] as usize + 2
+            }
+            _ => 2,
+        };
+        let start = frame.len();
+        frame.resize(start + rest, 0);
+        if stream.read_exact(&mut frame[start..]).await.is_err() {
+            return;
+        }
+        let (body, checksum) = frame.split_at(frame.len() - 2);
+        if crc(body).to_le_bytes() != checksum {
+            continue;
+        }
+        let (unit_id, function) = (body[0], body[1]);
+        let Some(request) = decode(function, &body[2..]) else {
+            continue;
+        };
+        if unit_id == 0 {
+            for device in devices.values() {
+                let _ = device.state.lock().unwrap().handle(request.clone());
+            }
+            continue;
+        }
+        let Some(device) = devices.get(&unit_id) else {
+            continue;
+        };
+        let result = device.state.lock().unwrap().handle(request);
+        let mut response = match result {
+            Ok(data) => [vec![unit_id, function], data].concat(),
+            Err(code) => vec![unit_id, function | 0x80, code],
+        };
+        response.extend(crc(&response).to_le_bytes());
+        if stream.write_all(&response).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// A Waveshare module served over an in-memory RTU link.
+#[derive(Debug, Clone, Default)]
+pub struct Simulator {
+    pub state: Arc<Mutex<DeviceState>>,
+}
+
+impl Simulator {
+    pub fn connect(unit_id: u8) -> (Simulator, ThreadSafeContext) {
+        let (mut simulators, context) = Simulator::connect_bus(&[unit_id]);
+        (simulators.remove(0), context)
+    }
+
+    /// One module per unit id, all sharing the same link.
+    pub fn connect_bus(unit_ids: &[u8]) -> (Vec<Simulator>, ThreadSafeContext) {
+        // Smaller than any frame, so every frame takes several writes as it
+        // would through a slow adapter
+        let (client, server) = tokio::io::duplex(4);
+        let simulators: Vec<Simulator> = unit_ids.iter().map(|_| Simulator::default()).collect();
+        let devices = unit_ids
+            .iter()
+            .copied()
+            .zip(simulators.iter().cloned())
+            .collect();
+        tokio::spawn(serve(server, devices));
+        let context = tokio_modbus::client::rtu::attach_slave(client, Slave(unit_ids[0]));
+        (simulators, ThreadSafeContext::new(context))
+    }
+}
@@ -0,0 +1,194 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio_modbus::Request;
+use waveshare::common::Channel;
+use waveshare::digital::{IoBank, Width};
+use waveshare::poller::Snapshot;
+use waveshare::watchdog::{
+    SafeAction, TripReason, Watchdog, WatchdogConfig, WatchdogError, WatchdogState,
+};
+
+fn config(safe_state: Vec<SafeAction>) -> WatchdogConfig {
+    WatchdogConfig {
+        heartbeat_timeout: Duration::from_millis(200),
+        max_consecutive_bus_errors: 3,
+        check_period: Duration::from_millis(20),
+        safe_state,
+    }
+}
+
+#[tokio::test]
+async fn heartbeat_timeout_closes_outputs() {
+    let (simulator, context) = Simulator::connect(1);
+    simulator.state.lock().unwrap().coils = [true; 8];
+
+    let watchdog = Watchdog::new(
+        context,
+        config(vec![SafeAction::CloseAllOutputs { unit_id: 1 }]),
+    )
+    .unwrap();
+    let (handle, task) = watchdog.spawn();
+    for _ in 0..5 {
+        handle.beat();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(*handle.state().borrow(), WatchdogState::Armed);
+
+    let reason = tokio::time::timeout(Duration::from_secs(2), task)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(reason, TripReason::HeartbeatTimeout(_)));
+    assert_eq!(simulator.state.lock().unwrap().coils, [false; 8]);
+    assert_eq!(
+        *handle.state().borrow(),
+        WatchdogState::SafeStateApplied(reason)
+    );
+}
+
+#[tokio::test]
+async fn persistent_bus_errors_apply_safe_state() {
+    let (simulator, context) = Simulator::connect(1);
+    let watchdog = Watchdog::new(
+        context,
+        config(vec![
+            SafeAction::DigitalOutputs {
+                unit_id: 1,
+                width: Width::W8,
+                outputs: IoBank::from_bits(0b100),
+            },
+            SafeAction::AnalogValue {
+                unit_id: 1,
+                channel: Channel::Channel3,
+                value: 4000,
+            },
+        ]),
+    )
+    .unwrap();
+    let (handle, task) = watchdog.spawn();
+    handle.beat();
+    handle.report_bus_error("timeout");
+    handle.report_bus_ok();
+    handle.report_bus_error("timeout");
+    handle.report_bus_error("timeout");
+    handle.report_bus_error("crc error");
+
+    let reason = tokio::time::timeout(Duration::from_secs(2), task)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        reason,
+        TripReason::BusErrors {
+            consecutive: 3,
+            last_error: "crc error".to_string()
+        }
+    );
+    let state = simulator.state.lock().unwrap();
+    assert_eq!(
+        state.coils,
+        [false, false, true, false, false, false, false, false]
+    );
+    assert_eq!(state.holding_registers.get(&3), Some(&4000));
+}
+
+#[tokio::test]
+async fn safe_state_is_retried_until_written() {
+    let (simulator, context) = Simulator::connect(1);
+    {
+        let mut state = simulator.state.lock().unwrap();
+        state.coils = [true; 8];
+        state.failing = true;
+    }
+    let watchdog = Watchdog::new(
+        context,
+        config(vec![SafeAction::CloseAllOutputs { unit_id: 1 }]),
+    )
+    .unwrap();
+    let (handle, task) = watchdog.spawn();
+    drop(handle);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!task.is_finished());
+    simulator.state.lock().unwrap().failing = false;
+
+    let reason = tokio::time::timeout(Duration::from_secs(2), task)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reason, TripReason::HeartbeatLost);
+    assert_eq!(simulator.state.lock().unwrap().coils, [false; 8]);
+}
+
+#[tokio::test]
+async fn dropping_handles_trips_while_monitoring_scans() {
+    let (simulator, context) = Simulator::connect(1);
+    simulator.state.lock().unwrap().coils = [true; 8];
+    let watchdog = Watchdog::new(
+        context,
+        config(vec![SafeAction::CloseAllOutputs { unit_id: 1 }]),
+    )
+    .unwrap();
+    let (handle, task) = watchdog.spawn();
+    let (scan, snapshots) = watch::channel(Snapshot::default());
+    let monitor = handle.monitor_scan(snapshots);
+    drop(handle);
+
+    // Well inside the heartbeat timeout
+    let reason = tokio::time::timeout(Duration::from_millis(150), task)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reason, TripReason::HeartbeatLost);
+    assert_eq!(simulator.state.lock().unwrap().coils, [false; 8]);
+
+    scan.send_modify(|_| {});
+    tokio::time::timeout(Duration::from_secs(1), monitor)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn zero_check_period_is_rejected() {
+    let (_simulator, context) = Simulator::connect(1);
+    let mut config = config(vec![SafeAction::CloseAllOutputs { unit_id: 1 }]);
+    config.check_period = Duration::ZERO;
+    assert!(matches!(
+        Watchdog::new(context, config),
+        Err(WatchdogError::InvalidCheckPeriod)
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn safe_outputs_cover_the_whole_board() {
+    let (simulator, context) = Simulator::connect(1);
+    let watchdog = Watchdog::new(
+        context,
+        config(vec![SafeAction::DigitalOutputs {
+            unit_id: 1,
+            width: Width::W16,
+            outputs: IoBank::from_bits(0x1_8001),
+        }]),
+    )
+    .unwrap();
+    let (handle, task) = watchdog.spawn();
+    drop(handle);
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    task.abort();
+
+    // Channel 16 is beyond the board and left out
+    let mut expected = [false; 16];
+    expected[0] = true;
+    expected[15] = true;
+    assert!(simulator
+        .state
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .any(|request| matches!(request, Request::WriteMultipleCoils(0, values) if values[..] == expected[..])));
+}
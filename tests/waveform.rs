@@ -0,0 +1,269 @@
+mod simulator;
+
+use simulator::Simulator;
+use std::time::Duration;
+use tokio_modbus::Request;
+use waveshare::analog_out::AnalogOutput;
+use waveshare::common::Channel;
+use waveshare::waveform::{self, PlaybackConfig, PlaybackOutcome, Waveform, WaveformError};
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+fn config(update_period: Duration, safe_value: u16) -> PlaybackConfig {
+    PlaybackConfig {
+        channels: vec![Channel::Channel0, Channel::Channel1],
+        update_period,
+        safe_value,
+        duration: None,
+    }
+}
+
+// A periodic waveform that keeps the outputs at `value`
+fn constant(value: f64) -> Waveform {
+    Waveform::Sine {
+        offset: value,
+        amplitude: 0.0,
+        period: Duration::from_secs(1),
+    }
+}
+
+fn register(simulator: &Simulator, addr: u16) -> Option<u16> {
+    simulator
+        .state
+        .lock()
+        .unwrap()
+        .holding_registers
+        .get(&addr)
+        .copied()
+}
+
+#[test]
+fn square() {
+    let square = Waveform::Square {
+        low: 0,
+        high: 100,
+        period: Duration::from_secs(1),
+        duty: 0.25,
+    };
+    assert_eq!(square.value_at(ms(0)), 100);
+    assert_eq!(square.value_at(ms(200)), 100);
+    assert_eq!(square.value_at(ms(250)), 0);
+    assert_eq!(square.value_at(ms(900)), 0);
+    assert_eq!(square.value_at(ms(1100)), 100);
+    assert_eq!(square.duration(), None);
+}
+
+#[test]
+fn triangle() {
+    let triangle = Waveform::Triangle {
+        low: 0,
+        high: 1000,
+        period: Duration::from_secs(1),
+    };
+    assert_eq!(triangle.value_at(ms(0)), 0);
+    assert_eq!(triangle.value_at(ms(250)), 500);
+    assert_eq!(triangle.value_at(ms(500)), 1000);
+    assert_eq!(triangle.value_at(ms(750)), 500);
+    assert_eq!(triangle.value_at(ms(1000)), 0);
+}
+
+#[test]
+fn sine() {
+    let sine = Waveform::Sine {
+        offset: 500.0,
+        amplitude: 500.0,
+        period: Duration::from_secs(1),
+    };
+    assert_eq!(sine.value_at(ms(0)), 500);
+    assert_eq!(sine.value_at(ms(250)), 1000);
+    assert_eq!(sine.value_at(ms(750)), 0);
+
+    // Values below zero are clamped
+    let sine = Waveform::Sine {
+        offset: 100.0,
+        amplitude: 500.0,
+        period: Duration::from_secs(1),
+    };
+    assert_eq!(sine.value_at(ms(750)), 0);
+}
+
+#[test]
+fn steps() {
+    let steps = Waveform::Steps(vec![(ms(1000), 10), (ms(2000), 20)]);
+    assert_eq!(steps.value_at(ms(0)), 10);
+    assert_eq!(steps.value_at(ms(999)), 10);
+    assert_eq!(steps.value_at(ms(1000)), 20);
+    assert_eq!(steps.value_at(ms(2999)), 20);
+    // The last value is held afterwards
+    assert_eq!(steps.value_at(ms(5000)), 20);
+    assert_eq!(steps.duration(), Some(ms(3000)));
+}
+
+#[test]
+fn points() {
+    let points = Waveform::Points(vec![(ms(0), 0), (ms(1000), 100), (ms(3000), 300)]);
+    assert_eq!(points.value_at(ms(0)), 0);
+    assert_eq!(points.value_at(ms(500)), 50);
+    assert_eq!(points.value_at(ms(2000)), 200);
+    assert_eq!(points.value_at(ms(5000)), 300);
+    assert_eq!(points.duration(), Some(ms(3000)));
+
+    // The first value is held until its offset
+    let points = Waveform::Points(vec![(ms(1000), 100), (ms(2000), 200)]);
+    assert_eq!(points.value_at(ms(0)), 100);
+}
+
+#[test]
+fn csv_points() {
+    let csv = "seconds,value\n0,0\n# ramp up\n0.5, 250\n\n1.5,1000\n";
+    assert_eq!(
+        Waveform::from_csv(csv.as_bytes()).unwrap(),
+        Waveform::Points(vec![(ms(0), 0), (ms(500), 250), (ms(1500), 1000)])
+    );
+}
+
+#[test]
+fn invalid_csv() {
+    assert!(matches!(
+        Waveform::from_csv("0,0\n1,10\n1,20\n".as_bytes()),
+        Err(WaveformError::Csv(3, _))
+    ));
+    assert!(matches!(
+        Waveform::from_csv("0,0\n2,10\n1,20\n".as_bytes()),
+        Err(WaveformError::Csv(3, _))
+    ));
+    // Only the first row may be a header
+    assert!(matches!(
+        Waveform::from_csv("0,0\nseconds,value\n".as_bytes()),
+        Err(WaveformError::Csv(2, _))
+    ));
+    assert!(matches!(
+        Waveform::from_csv("0;0\n".as_bytes()),
+        Err(WaveformError::Csv(1, _))
+    ));
+    assert!(matches!(
+        Waveform::from_csv("0,0\n0.5,-1\n".as_bytes()),
+        Err(WaveformError::Csv(2, _))
+    ));
+    assert!(matches!(
+        Waveform::from_csv("seconds,value\n".as_bytes()),
+        Err(WaveformError::InvalidWaveform(_))
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn finite_waveforms_complete_and_leave_the_safe_value() {
+    let (simulator, context) = Simulator::connect(1);
+    let steps = Waveform::Steps(vec![(ms(100), 10), (ms(100), 20)]);
+    let handle = waveform::play(AnalogOutput::new(1, context), steps, config(ms(50), 5)).unwrap();
+    let report = handle.finished().await.unwrap();
+
+    assert_eq!(report.outcome, PlaybackOutcome::Completed);
+    assert_eq!(report.updates, 4);
+    assert_eq!(report.requested_rate, 20.0);
+    let writes: Vec<Vec<u16>> = simulator
+        .state
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .filter_map(|request| match request {
+            // Both channels share one write
+            Request::WriteMultipleRegisters(0, values) => Some(values.to_vec()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        writes,
+        [[10, 10], [10, 10], [20, 20], [20, 20], [5, 5]].map(Vec::from)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn periodic_waveforms_stop_after_the_configured_duration() {
+    let (simulator, context) = Simulator::connect(1);
+    let config = PlaybackConfig {
+        duration: Some(ms(200)),
+        ..config(ms(50), 0)
+    };
+    let handle = waveform::play(AnalogOutput::new(1, context), constant(500.0), config).unwrap();
+    let report = handle.finished().await.unwrap();
+    assert_eq!(report.outcome, PlaybackOutcome::Completed);
+    assert_eq!(report.updates, 4);
+    assert_eq!(register(&simulator, 0), Some(0));
+}
+
+#[tokio::test(start_paused = true)]
+async fn stopping_leaves_the_safe_value() {
+    let (simulator, context) = Simulator::connect(1);
+    let mut handle = waveform::play(
+        AnalogOutput::new(1, context),
+        constant(500.0),
+        config(ms(50), 0),
+    )
+    .unwrap();
+    tokio::time::sleep(ms(220)).await;
+    assert_eq!(register(&simulator, 1), Some(500));
+
+    handle.stop();
+    let report = handle.finished().await.unwrap();
+    assert_eq!(report.outcome, PlaybackOutcome::Stopped);
+    assert_eq!(register(&simulator, 0), Some(0));
+    assert_eq!(register(&simulator, 1), Some(0));
+}
+
+#[tokio::test(start_paused = true)]
+async fn dropping_the_handle_leaves_the_safe_value() {
+    let (simulator, context) = Simulator::connect(1);
+    let handle = waveform::play(
+        AnalogOutput::new(1, context),
+        constant(500.0),
+        config(ms(50), 0),
+    )
+    .unwrap();
+    tokio::time::sleep(ms(220)).await;
+    assert_eq!(register(&simulator, 0), Some(500));
+
+    drop(handle);
+    tokio::time::sleep(ms(100)).await;
+    assert_eq!(register(&simulator, 0), Some(0));
+    assert_eq!(register(&simulator, 1), Some(0));
+}
+
+#[tokio::test(start_paused = true)]
+async fn write_errors_end_playback_and_leave_the_safe_value() {
+    let (simulator, context) = Simulator::connect(1);
+    {
+        let mut state = simulator.state.lock().unwrap();
+        state.holding_registers.insert(0, 500);
+        state.fail_next = 1;
+    }
+    let handle = waveform::play(
+        AnalogOutput::new(1, context),
+        constant(1000.0),
+        config(ms(50), 0),
+    )
+    .unwrap();
+    assert!(matches!(
+        handle.finished().await,
+        Err(WaveformError::AnalogOutput(_))
+    ));
+    assert_eq!(register(&simulator, 0), Some(0));
+    assert_eq!(simulator.state.lock().unwrap().requests.len(), 2);
+}
+
+#[tokio::test]
+async fn zero_update_period_is_rejected() {
+    let (simulator, context) = Simulator::connect(1);
+    assert!(matches!(
+        waveform::play(
+            AnalogOutput::new(1, context),
+            constant(500.0),
+            config(Duration::ZERO, 0),
+        ),
+        Err(WaveformError::InvalidUpdatePeriod)
+    ));
+    assert!(simulator.state.lock().unwrap().requests.is_empty());
+}